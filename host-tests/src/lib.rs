@@ -0,0 +1,41 @@
+//! 固件纯逻辑模块的主机测试入口
+//!
+//! 模块路径与固件保持一致 (`crate::protocol` 等)，测试代码写在各源文件的 `#[cfg(test)]` 中。
+
+#![no_std]
+
+#[path = "../../src/protocol.rs"]
+pub mod protocol;
+
+#[path = "../../src/frame.rs"]
+pub mod frame;
+
+#[path = "../../src/reliable.rs"]
+pub mod reliable;
+
+#[path = "../../src/auth.rs"]
+pub mod auth;
+
+#[path = "../../src/crypt.rs"]
+pub mod crypt;
+
+#[path = "../../src/modbus.rs"]
+pub mod modbus;
+
+#[path = "../../src/console.rs"]
+pub mod console;
+
+#[path = "../../src/stream.rs"]
+pub mod stream;
+
+#[path = "../../src/txqueue.rs"]
+pub mod txqueue;
+
+#[path = "../../src/deadband.rs"]
+pub mod deadband;
+
+#[path = "../../src/stats.rs"]
+pub mod stats;
+
+#[path = "../../src/dht.rs"]
+pub mod dht;
@@ -0,0 +1,357 @@
+//! 下行帧认证 (HMAC-SHA256)
+//!
+//! 带认证标志的 v2 帧在 Payload 之后附加 `COUNTER(4) MAC(8)`：
+//! MAC 为 HMAC-SHA256(认证密钥, SOF..COUNTER) 的前 8 字节，COUNTER 由上位机单调递增。
+//! 认证密钥与加密的会话密钥一样由预置密钥和上电随机数派生 (见 `derive_key`)，
+//! 下位机复位后旧帧的 MAC 不再有效，因此计数器只需在 RAM 中保存。
+
+use crate::frame::{AUTH_FLAG, AUTH_TRAILER_LEN, FrameParts, crc16_ccitt};
+use crate::protocol::MessageType;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+
+/// 截断后的 MAC 长度
+pub const MAC_LEN: usize = 8;
+/// 共享密钥长度
+pub const KEY_LEN: usize = 32;
+/// 上电随机数长度
+pub const BOOT_NONCE_LEN: usize = 8;
+
+/// 认证失败的原因，随 SecurityEvent 上报
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum AuthError {
+    /// MAC 校验失败 (伪造或密钥不符)
+    BadMac = 0x01,
+    /// 计数器没有递增 (重放)
+    Replay = 0x02,
+    /// 要求认证的消息没有带认证
+    Missing = 0x03,
+}
+
+impl TryFrom<u8> for AuthError {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(AuthError::BadMac),
+            0x02 => Ok(AuthError::Replay),
+            0x03 => Ok(AuthError::Missing),
+            other => Err(other),
+        }
+    }
+}
+
+/// 重放保护：记录最近一次通过认证的计数器
+///
+/// 计数器只保存在 RAM 中，下位机复位后重新从头接受；复位后认证密钥随上电随机数改变，
+/// 上一次上电录下的帧无法通过 MAC 校验。
+pub struct ReplayGuard {
+    last: Option<(u32, [u8; MAC_LEN])>,
+}
+
+impl ReplayGuard {
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// 校验帧的 MAC 与计数器，通过后记录计数器
+    ///
+    /// 与上一帧完全相同 (计数器和 MAC 都相同) 的命令帧视为上位机重传而放行，
+    /// 由命令序号判重保证不会重复执行；其它消息没有判重，相同的帧按重放拒绝。
+    pub fn verify(&mut self, key: &[u8], parts: &FrameParts) -> Result<(), AuthError> {
+        let Some(auth) = &parts.auth else {
+            return Err(AuthError::Missing);
+        };
+        let mut expected = hmac(key);
+        expected.update(auth.signed);
+        // 常数时间比较，避免通过耗时猜测 MAC
+        expected
+            .verify_truncated_left(auth.mac)
+            .map_err(|_| AuthError::BadMac)?;
+        let mac = auth.mac.try_into().map_err(|_| AuthError::BadMac)?;
+        self.check(auth.counter, mac, parts.msg_type)
+    }
+
+    /// 对已经验证过 MAC 的帧做计数器检查 (加密帧由 AEAD 标签验证)
+    pub fn check(
+        &mut self,
+        counter: u32,
+        mac: [u8; MAC_LEN],
+        msg_type: u8,
+    ) -> Result<(), AuthError> {
+        let retransmit = msg_type == MessageType::Command as u8;
+        match self.last {
+            Some((last, last_mac)) if retransmit && counter == last && last_mac == mac => Ok(()),
+            Some((last, _)) if counter <= last => Err(AuthError::Replay),
+            _ => {
+                self.last = Some((counter, mac));
+                Ok(())
+            }
+        }
+    }
+}
+
+impl Default for ReplayGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 给一个已编码的 v2 帧加上认证，返回新的帧长度
+///
+/// `buffer[..len]` 必须是合法的 v2 帧，且尾部至少还有 `AUTH_TRAILER_LEN` 字节空间。
+/// 下位机只做校验，此函数供主机测试和上位机参考实现使用。
+#[allow(dead_code)]
+pub fn sign(key: &[u8], counter: u32, buffer: &mut [u8], len: usize) -> usize {
+    // 去掉原 CRC，置认证标志，附加计数器和 MAC
+    let body_end = len - 2;
+    buffer[1] |= AUTH_FLAG;
+    buffer[body_end..body_end + 4].copy_from_slice(&counter.to_be_bytes());
+    let mac_start = body_end + 4;
+    let tag = mac(key, &buffer[..mac_start]);
+    buffer[mac_start..mac_start + MAC_LEN].copy_from_slice(&tag);
+
+    let crc_idx = body_end + AUTH_TRAILER_LEN;
+    let crc = crc16_ccitt(&buffer[..crc_idx]);
+    buffer[crc_idx..crc_idx + 2].copy_from_slice(&crc.to_be_bytes());
+    crc_idx + 2
+}
+
+/// 由预置密钥和上电随机数派生本次上电的认证密钥
+pub fn derive_key(secret: &[u8], boot_nonce: &[u8; BOOT_NONCE_LEN]) -> [u8; KEY_LEN] {
+    let mut mac = hmac(secret);
+    mac.update(b"iot-auth");
+    mac.update(boot_nonce);
+    mac.finalize().into_bytes().into()
+}
+
+/// 截断的 HMAC-SHA256
+pub fn mac(key: &[u8], data: &[u8]) -> [u8; MAC_LEN] {
+    let full = hmac_sha256(key, data);
+    let mut out = [0u8; MAC_LEN];
+    out.copy_from_slice(&full[..MAC_LEN]);
+    out
+}
+
+/// 构建时从十六进制字符串解析预置密钥，未提供时为全 0
+pub const fn parse_key(hex: Option<&str>) -> [u8; KEY_LEN] {
+    let mut key = [0u8; KEY_LEN];
+    let Some(hex) = hex else {
+        return key;
+    };
+    let bytes = hex.as_bytes();
+    assert!(bytes.len() == KEY_LEN * 2, "auth key must be 64 hex digits");
+    let mut i = 0;
+    while i < KEY_LEN {
+        key[i] = (hex_digit(bytes[2 * i]) << 4) | hex_digit(bytes[2 * i + 1]);
+        i += 1;
+    }
+    key
+}
+
+const fn hex_digit(c: u8) -> u8 {
+    match c {
+        b'0'..=b'9' => c - b'0',
+        b'a'..=b'f' => c - b'a' + 10,
+        b'A'..=b'F' => c - b'A' + 10,
+        _ => panic!("auth key must be 64 hex digits"),
+    }
+}
+
+/// HMAC-SHA256，任意长度的密钥
+pub fn hmac_sha256(key: &[u8], data: &[u8]) -> [u8; 32] {
+    let mut mac = hmac(key);
+    mac.update(data);
+    mac.finalize().into_bytes().into()
+}
+
+pub fn sha256(data: &[u8]) -> [u8; 32] {
+    Sha256::digest(data).into()
+}
+
+fn hmac(key: &[u8]) -> Hmac<Sha256> {
+    // HMAC 接受任意长度的密钥，不会失败
+    <Hmac<Sha256> as Mac>::new_from_slice(key).unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{FrameError, FrameVersion, check_frame, encode_msg, frame_parts};
+    use crate::protocol::{CommandAck, TxMessage};
+
+    fn hex(s: &str) -> heapless::Vec<u8, 128> {
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn sha256_vectors() {
+        assert_eq!(
+            sha256(b"abc").as_slice(),
+            hex("ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad").as_slice()
+        );
+        // 跨两个块
+        assert_eq!(
+            sha256(b"abcdbcdecdefdefgefghfghighijhijkijkljklmklmnlmnomnopnopq").as_slice(),
+            hex("248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1").as_slice()
+        );
+        assert_eq!(
+            sha256(b"").as_slice(),
+            hex("e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855").as_slice()
+        );
+    }
+
+    #[test]
+    fn hmac_rfc4231() {
+        // Test Case 1
+        assert_eq!(
+            hmac_sha256(&[0x0b; 20], b"Hi There").as_slice(),
+            hex("b0344c61d8db38535ca8afceaf0bf12b881dc200c9833da726e9376c2e32cff7").as_slice()
+        );
+        // Test Case 2
+        assert_eq!(
+            hmac_sha256(b"Jefe", b"what do ya want for nothing?").as_slice(),
+            hex("5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843").as_slice()
+        );
+        // Test Case 6: 长于块大小的密钥
+        assert_eq!(
+            hmac_sha256(
+                &[0xaa; 131],
+                b"Test Using Larger Than Block-Size Key - Hash Key First"
+            )
+            .as_slice(),
+            hex("60e431591ee0b67f0d8a26aacbf5b77f8e0bc6213728c5140546040f0ee37f54").as_slice()
+        );
+    }
+
+    #[test]
+    fn parse_key_hex() {
+        assert_eq!(parse_key(None), [0; KEY_LEN]);
+        let key = parse_key(Some(
+            "000102030405060708090a0b0c0d0e0f101112131415161718191A1B1C1D1E1F",
+        ));
+        assert_eq!(key[0x0A], 0x0A);
+        assert_eq!(key[0x1F], 0x1F);
+    }
+
+    const KEY: [u8; KEY_LEN] = [0x5A; KEY_LEN];
+
+    #[test]
+    fn key_bound_to_boot_nonce() {
+        let nonce = [1, 2, 3, 4, 5, 6, 7, 8];
+        assert_eq!(
+            derive_key(&KEY, &nonce),
+            hmac_sha256(&KEY, b"iot-auth\x01\x02\x03\x04\x05\x06\x07\x08")
+        );
+
+        // 上一次上电签名的帧在复位后 (上电随机数改变) 无法通过校验
+        let mut buf = [0u8; 64];
+        let len = encode_msg(
+            &TxMessage::Heartbeat { uptime_s: 1 },
+            FrameVersion::V2,
+            0,
+            false,
+            &mut buf,
+        );
+        let len = sign(&derive_key(&KEY, &nonce), 1, &mut buf, len);
+        let next_boot = derive_key(&KEY, &[1, 2, 3, 4, 5, 6, 7, 9]);
+        assert_eq!(
+            ReplayGuard::new().verify(&next_boot, &frame_parts(&buf[..len])),
+            Err(AuthError::BadMac)
+        );
+    }
+
+    fn signed_frame(counter: u32, seq: u8) -> ([u8; 64], usize) {
+        signed_msg(counter, seq, MessageType::Command)
+    }
+
+    /// 借用 CommandAck 的编码构造一个带认证的帧，TYPE 换成 `msg_type`
+    fn signed_msg(counter: u32, seq: u8, msg_type: MessageType) -> ([u8; 64], usize) {
+        let mut buf = [0u8; 64];
+        let ack = CommandAck::rejected(0x10, crate::protocol::NackReason::Busy, seq);
+        let len = encode_msg(&TxMessage::Ack(ack), FrameVersion::V2, 0, false, &mut buf);
+        buf[4] = msg_type as u8;
+        let len = sign(&KEY, counter, &mut buf, len);
+        (buf, len)
+    }
+
+    #[test]
+    fn signed_frame_verifies_once() {
+        let (buf, len) = signed_frame(7, 1);
+        assert!(matches!(check_frame(&buf[..len]), FrameError::Valid(l) if l == len));
+        let parts = frame_parts(&buf[..len]);
+        assert_eq!(parts.auth.as_ref().map(|a| a.counter), Some(7));
+
+        let mut guard = ReplayGuard::new();
+        assert_eq!(guard.verify(&KEY, &parts), Ok(()));
+        // 同一帧重传放行
+        assert_eq!(guard.verify(&KEY, &parts), Ok(()));
+
+        // 更小的计数器被拒绝，即使 MAC 正确
+        let (old, old_len) = signed_frame(6, 2);
+        assert_eq!(
+            guard.verify(&KEY, &frame_parts(&old[..old_len])),
+            Err(AuthError::Replay)
+        );
+        // 相同计数器、不同内容同样是重放
+        let (other, other_len) = signed_frame(7, 3);
+        assert_eq!(
+            guard.verify(&KEY, &frame_parts(&other[..other_len])),
+            Err(AuthError::Replay)
+        );
+
+        let (next, next_len) = signed_frame(8, 4);
+        assert_eq!(guard.verify(&KEY, &frame_parts(&next[..next_len])), Ok(()));
+    }
+
+    #[test]
+    fn identical_non_command_frame_is_replay() {
+        let mut guard = ReplayGuard::new();
+        let (buf, len) = signed_msg(3, 1, MessageType::Configure);
+        let parts = frame_parts(&buf[..len]);
+        assert_eq!(guard.verify(&KEY, &parts), Ok(()));
+        // Configure 没有按 SEQ 判重，重传会被再次执行，只能按重放拒绝
+        assert_eq!(guard.verify(&KEY, &parts), Err(AuthError::Replay));
+    }
+
+    #[test]
+    fn forged_frames_are_rejected() {
+        let mut guard = ReplayGuard::new();
+
+        // 错误密钥
+        let (buf, len) = signed_frame(1, 1);
+        assert_eq!(
+            guard.verify(&[0u8; KEY_LEN], &frame_parts(&buf[..len])),
+            Err(AuthError::BadMac)
+        );
+
+        // 篡改 Payload 后重新计算 CRC
+        let (mut buf, len) = signed_frame(1, 1);
+        buf[6] ^= 0x01;
+        let crc = crc16_ccitt(&buf[..len - 2]);
+        buf[len - 2..len].copy_from_slice(&crc.to_be_bytes());
+        assert_eq!(
+            guard.verify(&KEY, &frame_parts(&buf[..len])),
+            Err(AuthError::BadMac)
+        );
+
+        // 没有认证的帧
+        let mut plain = [0u8; 64];
+        let len = encode_msg(
+            &TxMessage::Heartbeat { uptime_s: 1 },
+            FrameVersion::V2,
+            0,
+            false,
+            &mut plain,
+        );
+        assert_eq!(
+            guard.verify(&KEY, &frame_parts(&plain[..len])),
+            Err(AuthError::Missing)
+        );
+    }
+}
@@ -1,9 +1,10 @@
 use crate::config;
-use defmt;
+use crate::protocol::SensorTag;
+use crate::sampling;
 use embassy_stm32::i2c::I2c;
 use embassy_stm32::i2c::Master;
 use embassy_stm32::mode::Async;
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 
 pub type I2cDriver = I2c<'static, Async, Master>;
 
@@ -11,8 +12,6 @@ pub type I2cDriver = I2c<'static, Async, Master>;
 /// 该任务获取 I2C 驱动的所有权，进行传感器初始化和周期性读取光照数据
 #[embassy_executor::task]
 pub async fn bh1750_read(mut i2c: I2cDriver) {
-    let tx_sender = crate::config::UART_TX_CHANNEL.sender();
-    let ui_sender = crate::config::UI_CHANNEL.sender();
     defmt::info!("BH1750 任务已启动");
 
     // 初始化传感器：首先向设备发送通电命令
@@ -35,8 +34,9 @@ pub async fn bh1750_read(mut i2c: I2cDriver) {
     // 等待传感器稳定 (高分辨率模式需要约 180ms)
     Timer::after(Duration::from_millis(180)).await;
 
-    // 进入无限循环，每秒读取一次光照数据
+    // 进入无限循环，按采样间隔读取光照数据
     loop {
+        let started = Instant::now();
         // 准备缓冲区用于存储从传感器读取的 2 字节数据
         let mut iic_buf: [u8; 2] = [0; 2];
 
@@ -46,24 +46,17 @@ pub async fn bh1750_read(mut i2c: I2cDriver) {
                 // 将读取的字节数据转换为 16 位无符号整数
                 let raw_data: u16 = ((iic_buf[0] as u16) << 8) | (iic_buf[1] as u16);
                 // 根据 BH1750 数据手册，将原始数据转换为光照强度 (lux)
-                // 高分辨率模式下，lux = raw_data / 1.2
-                let lux: f32 = (raw_data as f32) / 1.2;
-                defmt::info!("光照强度 {} lux，原始数据 {}", lux, raw_data);
+                // 高分辨率模式下，lux = raw_data / 1.2 = raw_data * 5 / 6
+                // 用整数运算：浮点除法要额外链接数百字节的软件实现
+                let lux_u16 = (u32::from(raw_data) * 5 / 6) as u16;
+                defmt::info!("光照强度 {} lux，原始数据 {}", lux_u16, raw_data);
 
-                // 上报 (Cast raw data directly as protocol requests u16, or send calculated?)
-                // API Document says LightIntensity (u16).
-                // Let's send the raw/1.2 casted to u16.
-                let lux_u16 = lux as u16;
-                let report = crate::protocol::TxMessage::Sensor(
-                    crate::protocol::SensorData::LightIntensity(lux_u16),
-                );
-                tx_sender.send(report.clone()).await;
-                let _ = ui_sender.try_send(report);
+                crate::report::publish(crate::protocol::SensorData::LightIntensity(lux_u16));
             }
             Err(e) => defmt::info!("读取数据失败：{:?}", e),
         }
 
-        // 等待 1 秒后进行下一次读取
-        Timer::after(Duration::from_secs(1)).await;
+        // 等待采样间隔后进行下一次读取
+        sampling::wait(SensorTag::LightIntensity, started).await;
     }
 }
@@ -1,50 +1,99 @@
-use crate::config::{COMMAND_CHANNEL, UART_TX_CHANNEL};
-use crate::protocol::{ActuatorFeedback, ActuatorTag, CommandAck, ControlCommand, TxMessage};
+use crate::config::{
+    COMMAND_CHANNEL, FAILSAFE_POLICY, INTERLOCKS, MODBUS_ACK_CHANNEL, UART_TX_CHANNEL,
+};
+use crate::console::Reply;
+use crate::diag;
+use crate::link::{LINK_STATE, LinkState};
+use crate::protocol::{
+    AckMode, ActuatorFeedback, ActuatorTag, CommandAck, ControlCommand, NackReason, TxMessage,
+};
+use crate::store;
+use crate::uart;
 use embassy_executor::task;
+use embassy_futures::select::{Either3, select3};
 use embassy_stm32::gpio::{Level, Speed};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::{Channel, Sender};
-use embassy_time::{Duration, Timer};
+use embassy_time::{Duration, Instant, Timer};
 
 // 定义每种执行器的命令通道
 // 这些通道不需要全局，因为它们只在 main 中初始化并传递，或者为了方便，我们在 command.rs 内部定义辅助结构
 // 为了简洁，这里我们可以定义一个 Channel 类型别名
 pub type ActuatorChannel = Channel<CriticalSectionRawMutex, ControlCommand, 2>;
 
+type ActuatorSender = Sender<'static, CriticalSectionRawMutex, ControlCommand, 2>;
+
+// 打开命令是否违反互锁配置
+// 执行器还有排队的命令时按最后分发的命令状态判断，否则按实际状态判断。
+// 执行器任务取出命令后到更新 store 之间没有 await，两者对命令任务总是一致的
+fn interlocked(cmd: &ControlCommand, senders: &[ActuatorSender; 4], commanded: &[bool; 4]) -> bool {
+    let on = |actuator: ActuatorTag| {
+        if senders[actuator.index()].is_empty() {
+            store::is_on(actuator)
+        } else {
+            commanded[actuator.index()]
+        }
+    };
+    cmd.state
+        && INTERLOCKS
+            .iter()
+            .any(|&(a, b)| (a == cmd.actuator && on(b)) || (b == cmd.actuator && on(a)))
+}
+
 #[task]
 pub async fn command_task(
-    fan_sender: Sender<'static, CriticalSectionRawMutex, ControlCommand, 2>,
-    pump_sender: Sender<'static, CriticalSectionRawMutex, ControlCommand, 2>,
-    light_sender: Sender<'static, CriticalSectionRawMutex, ControlCommand, 2>,
-    buzzer_sender: Sender<'static, CriticalSectionRawMutex, ControlCommand, 2>,
+    fan_sender: ActuatorSender,
+    pump_sender: ActuatorSender,
+    light_sender: ActuatorSender,
+    buzzer_sender: ActuatorSender,
 ) {
     let receiver = COMMAND_CHANNEL.receiver();
-    let tx_sender = UART_TX_CHANNEL.sender();
+    let tx_sender = &UART_TX_CHANNEL;
+    // 按 ActuatorTag::index 排列
+    let senders = [fan_sender, pump_sender, light_sender, buzzer_sender];
+    // 最后分发给各执行器的命令状态，互锁检查不必等执行器任务运行
+    let mut commanded = [false; 4];
 
     loop {
         let cmd = receiver.receive().await;
+        diag::incr(&diag::COMMANDS);
+
+        // 1. 分发给具体的 Actuator Task
+        let index = cmd.actuator.index();
+        let ack = if interlocked(&cmd, &senders, &commanded) {
+            CommandAck::rejected(cmd.actuator as u8, NackReason::Interlocked, cmd.seq)
+        } else if senders[index].try_send(cmd).is_err() {
+            // 执行器还在处理之前的命令 (如脉冲未结束) 且队列已满
+            CommandAck::rejected(cmd.actuator as u8, NackReason::Busy, cmd.seq)
+        } else {
+            commanded[index] = cmd.state;
+            CommandAck::accepted(&cmd)
+        };
 
-        // 1. 发送 ACK
+        // 2. 发送 ACK
         // 这里的 ACK 表示"收到并分发成功"，并不代表物理动作完成，但也足够了
         // 如果需要执行后 ACK，需要 ActuatorFeedback
-        let ack = CommandAck {
-            actuator: cmd.actuator,
-            success: true,
-        };
-        tx_sender.send(TxMessage::Ack(ack)).await;
-
-        // 2. 分发给具体的 Actuator Task
-        let target_sender = match cmd.actuator {
-            ActuatorTag::Fan => &fan_sender,
-            ActuatorTag::Pump => &pump_sender,
-            ActuatorTag::Light => &light_sender,
-            ActuatorTag::Buzzer => &buzzer_sender,
-        };
-
-        target_sender.send(cmd).await;
+        match cmd.ack {
+            AckMode::Frame => uart::send_ack(ack).await,
+            AckMode::Text => tx_sender.send(TxMessage::Console(Reply::Ack(ack))).await,
+            AckMode::Modbus => MODBUS_ACK_CHANNEL.send(ack).await,
+            AckMode::Silent => {}
+        }
     }
 }
 
+/// 链路丢失时执行器的失效保护策略
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FailSafePolicy {
+    /// 强制关闭 (取消进行中的脉冲)
+    ForceOff,
+    /// 保持当前状态 (进行中的脉冲照常结束)
+    Hold,
+    /// 强制打开
+    #[allow(dead_code)] // 当前配置未使用
+    ForceOn,
+}
+
 // 通用的执行器任务
 // active_level: true 表示高电平触发/打开，false 表示低电平触发/打开
 use embassy_sync::channel::Receiver;
@@ -53,6 +102,7 @@ use embassy_sync::channel::Receiver;
 pub async fn actuator_task(
     mut flex: embassy_stm32::gpio::Flex<'static>,
     receiver: Receiver<'static, CriticalSectionRawMutex, ControlCommand, 2>,
+    actuator: ActuatorTag,
     active_high: bool,
 ) {
     // 初始状态 OFF
@@ -62,48 +112,87 @@ pub async fn actuator_task(
     flex.set_as_output(Speed::Low);
     flex.set_level(initial_level);
 
-    let tx_sender = UART_TX_CHANNEL.sender();
+    let tx_sender = &UART_TX_CHANNEL;
     let ui_sender = crate::config::UI_CHANNEL.sender();
+    // LINK_STATE 的接收者数量足够 4 个执行器任务使用
+    let mut link = LINK_STATE.receiver().unwrap();
+    let policy = FAILSAFE_POLICY[actuator.index()];
+
+    let mut state = false;
+    let mut failsafe = false;
+    // 进行中的脉冲的结束时刻
+    let mut pulse_end: Option<Instant> = None;
 
     loop {
-        let cmd = receiver.receive().await;
+        let pulse_timer = async {
+            match pulse_end {
+                Some(end) => Timer::at(end).await,
+                None => core::future::pending().await,
+            }
+        };
+
+        match select3(receiver.receive(), link.changed(), pulse_timer).await {
+            Either3::First(cmd) => {
+                // 新命令覆盖进行中的脉冲，并重新接受上位机控制
+                state = cmd.state;
+                failsafe = false;
+                // 处理 Pulse
+                // 如果 state = true 且 duration > 0
+                pulse_end = (cmd.state && cmd.duration_ms > 0)
+                    .then(|| Instant::now() + Duration::from_millis(cmd.duration_ms as u64));
+            }
+            Either3::Second(LinkState::Lost) => {
+                crate::fmt::warn!(
+                    "Link lost, applying fail-safe to actuator {}",
+                    actuator as u8
+                );
+                failsafe = true;
+                match policy {
+                    FailSafePolicy::ForceOff => {
+                        state = false;
+                        pulse_end = None;
+                    }
+                    FailSafePolicy::ForceOn => {
+                        state = true;
+                        pulse_end = None;
+                    }
+                    FailSafePolicy::Hold => {}
+                }
+            }
+            Either3::Second(_) => {
+                if !failsafe {
+                    continue;
+                }
+                // 链路恢复，重新接受上位机控制
+                failsafe = false;
+            }
+            Either3::Third(()) => {
+                // 脉冲结束，恢复 OFF
+                pulse_end = None;
+                state = false;
+            }
+        }
 
         // 执行动作
-        let target_level = if cmd.state {
-            if active_high { Level::High } else { Level::Low }
+        let target_level = if state == active_high {
+            Level::High
         } else {
-            if active_high { Level::Low } else { Level::High }
+            Level::Low
         };
-
         flex.set_level(target_level);
 
         // 上报状态
         let feedback = ActuatorFeedback {
-            actuator: cmd.actuator,
-            state: cmd.state,
+            actuator,
+            state,
+            failsafe,
+            timestamp_ms: Some(Instant::now().as_millis() as u32),
         };
+        store::set_actuator(feedback);
         let msg = TxMessage::Actuator(feedback);
-        tx_sender.send(msg.clone()).await;
-        let _ = ui_sender.try_send(msg);
-
-        // 处理 Pulse
-        // 如果 state = true 且 duration > 0
-        if cmd.state && cmd.duration_ms > 0 {
-            // 等待时间
-            Timer::after(Duration::from_millis(cmd.duration_ms as u64)).await;
-
-            // 恢复 OFF
-            let off_level = if active_high { Level::Low } else { Level::High };
-            flex.set_level(off_level);
-
-            // 上报状态 OFF
-            let feedback_off = ActuatorFeedback {
-                actuator: cmd.actuator,
-                state: false,
-            };
-            let msg_off = TxMessage::Actuator(feedback_off);
-            tx_sender.send(msg_off.clone()).await;
-            let _ = ui_sender.try_send(msg_off);
+        tx_sender.send(msg).await;
+        if ui_sender.try_send(msg).is_err() {
+            diag::incr(&diag::UI_DROPS);
         }
     }
 }
@@ -1,4 +1,10 @@
-use crate::protocol::{ControlCommand, TxMessage};
+use crate::auth;
+use crate::command::FailSafePolicy;
+use crate::dht::DhtVariant;
+use crate::protocol::{ActuatorTag, CommandAck, ControlCommand, Deadband, SensorTag, TxMessage};
+use crate::report::ReportMode;
+use crate::rtu::LinkProtocol;
+use crate::uplink::Uplink;
 use embassy_stm32::{bind_interrupts, peripherals, rcc, time::mhz};
 use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
 use embassy_sync::channel::Channel;
@@ -43,11 +49,103 @@ pub const BH1750_ADDR: u8 = 0x23; //接地时的地址
 pub const CMD_POWER_ON: u8 = 0x01u8; //通电指令
 pub const CMD_H_RES_MODE: u8 = 0x10; //连续高分辨率模式
 
+//系统时钟 (MHz)：HSE 8MHz × PLL 9，DHT 边沿时间戳 (DWT 周期计数) 据此换算为微秒
+pub const SYSCLK_MHZ: u32 = 72;
+
+//温湿度传感器型号 (PA1)：DHT11、DHT22 或 AM2302，决定数据解码、量程和最小采样间隔
+pub const DHT_VARIANT: DhtVariant = DhtVariant::Dht11;
+
+//各传感器的默认采样间隔 (ms)，上位机可用 SetInterval 在运行时修改
+pub const SOIL_INTERVAL_MS: u32 = 1000;
+pub const DHT_INTERVAL_MS: u32 = 2000;
+pub const BH1750_INTERVAL_MS: u32 = 1000;
+//最小采样间隔 (ms)：BH1750 高分辨率模式一次测量约 180ms；温湿度传感器见 `DhtVariant::min_interval_ms`
+pub const SOIL_MIN_INTERVAL_MS: u32 = 100;
+pub const BH1750_MIN_INTERVAL_MS: u32 = 200;
+
+//传感器死区 (变化上报)，按 SoilMoisture, Temperature, Humidity, LightIntensity 排列，
+//None 表示每次采样都上报；上位机可用 SetDeadband 在运行时修改
+pub const SENSOR_DEADBANDS: [Option<Deadband>; 4] = [None; 4];
+
+//窗口统计 (SensorStats) 的默认窗口长度 (s)，0 = 关闭；上位机可用 Configure 在运行时修改
+pub const STATS_WINDOW_S: u32 = 0;
+
+//可靠上行模式：关键上行消息等待上位机 HostAck 并超时重传 (仅 v2 链路生效)；
+//上位机必须支持 HostAck 才能打开，否则每条执行器反馈都会被重传
+pub const RELIABLE_UPLINK: bool = false;
+
+//传感器上报模式：逐条上报，或按周期打包所有传感器最新值为一帧
+pub const SENSOR_REPORT_MODE: ReportMode = ReportMode::PerReading;
+//快照的最短周期 (ms)；实际周期取它与最短采样间隔中的较大者，随 SetInterval 变化
+pub const SNAPSHOT_PERIOD_MS: u64 = 2000;
+
+//执行器互锁：每对执行器不允许同时打开，打开其中一个时若另一个已开则拒绝 (NackReason::Interlocked)
+pub const INTERLOCKS: &[(ActuatorTag, ActuatorTag)] = &[];
+
+//心跳周期，以及多久没有收到上位机合法帧判定为链路丢失
+pub const HEARTBEAT_PERIOD_MS: u64 = 5000;
+pub const LINK_TIMEOUT_MS: u64 = 15000;
+
+//链路丢失 (超过 LINK_TIMEOUT_MS 未收到合法帧) 时各执行器的失效保护策略，按 Fan, Pump, Light, Buzzer 排列；
+//上位机发过心跳后才会判定链路丢失，从不发心跳的 v1 上位机不会触发失效保护
+pub const FAILSAFE_POLICY: [FailSafePolicy; 4] = [
+    FailSafePolicy::ForceOff,
+    FailSafePolicy::ForceOff,
+    FailSafePolicy::Hold,
+    FailSafePolicy::ForceOff,
+];
+
+//为 true 时改变设备状态的下行帧 (命令、配置、HostAck、诊断清零) 必须带认证 (HMAC-SHA256 + 单调计数器)，否则拒绝并上报 SecurityEvent
+//带认证的帧无论此开关如何都会校验
+pub const REQUIRE_AUTH: bool = false;
+//为 true 时上行帧 (DeviceInfo 除外) 一律加密为 v2 加密帧，下行只接受加密帧 (GetDeviceInfo 除外)
+//为 false 时明文帧照常收发，收到的加密帧仍会解密处理
+pub const ENCRYPTION: bool = false;
+//预置共享密钥，构建时由环境变量 IOT_AUTH_KEY (64 位十六进制) 提供，未提供时为全 0
+//认证密钥和加密的会话密钥都由它和上电随机数派生
+pub const AUTH_KEY: [u8; auth::KEY_LEN] = auth::parse_key(option_env!("IOT_AUTH_KEY"));
+//要求认证或加密时必须提供密钥，全 0 的公开密钥起不到任何保护作用
+const _: () = assert!(
+    !(REQUIRE_AUTH || ENCRYPTION) || option_env!("IOT_AUTH_KEY").is_some(),
+    "REQUIRE_AUTH / ENCRYPTION need a shared key: build with IOT_AUTH_KEY=<64 hex digits>"
+);
+
+//USART1 上的链路协议：自定义帧，或 Modbus RTU 从站 (寄存器映射见 modbus 模块)
+pub const LINK_PROTOCOL: LinkProtocol = LinkProtocol::Frames;
+//Modbus RTU 从站地址 (1..=247)
+pub const MODBUS_SLAVE_ADDR: u8 = 1;
+//帧收到一半后超过这段时间 (ms) 没有新字节即跳过残帧的 SOF 重新同步，免得它吞掉下一帧的开头；0 表示不超时
+//ESP-01S 等 Wi-Fi 透传模块会把一帧拆成几个包转发，包间停顿可达数百 ms，超时必须远大于这个间隔
+pub const RX_FRAME_TIMEOUT_MS: u64 = 1000;
+
+//文本控制台：在串口终端输入 `fan on`、`get sensors` 等命令行调试 (输入 help 查看全部命令)
+//控制台不经过认证，默认关闭；不能与 REQUIRE_AUTH / ENCRYPTION 同时开启，NODE_ADDRESS 开启时自动禁用
+pub const CONSOLE: bool = false;
+const _: () = assert!(
+    !(CONSOLE && (REQUIRE_AUTH || ENCRYPTION)),
+    "CONSOLE bypasses authentication: turn it off when REQUIRE_AUTH or ENCRYPTION is on"
+);
+
+//RS-485 多机总线上的本机地址 (1..=0xFE)，上行帧带此地址，只处理发给本机或广播 (0xFF) 的下行帧
+//None 表示点对点连接：上行帧不带地址，下行帧不论地址一律处理
+pub const NODE_ADDRESS: Option<u8> = None;
+//多机总线上节点不主动发送，只在收到发给本机的帧后的这段时间 (ms) 内发送上行帧，其余上行消息丢弃
+pub const POLL_REPLY_WINDOW_MS: u64 = 100;
+
+//本机安装的传感器和执行器，随 DeviceInfo 上报
+pub const SENSORS_PRESENT: &[SensorTag] = &SensorTag::ALL;
+pub const ACTUATORS_PRESENT: &[ActuatorTag] = &ActuatorTag::ALL;
+
 //全局静态变量
 pub static CHANNEL_DHT11: Channel<CriticalSectionRawMutex, [u8; 5], 2> = Channel::new();
 
 //pub type SharedTx<'d> = Mutex<CriticalSectionRawMutex, UartTx<'d, Async>>;
 
-pub static UART_TX_CHANNEL: Channel<CriticalSectionRawMutex, TxMessage, 8> = Channel::new();
+/// 上行消息队列，按优先级发送 (见 `txqueue`)
+pub static UART_TX_CHANNEL: Uplink<8> = Uplink::new();
 pub static UI_CHANNEL: Channel<CriticalSectionRawMutex, TxMessage, 16> = Channel::new();
 pub static COMMAND_CHANNEL: Channel<CriticalSectionRawMutex, ControlCommand, 4> = Channel::new();
+/// Modbus 写线圈命令的 ACK，由命令任务交回 Modbus 从站任务
+pub static MODBUS_ACK_CHANNEL: Channel<CriticalSectionRawMutex, CommandAck, 1> = Channel::new();
+/// 上位机 HostAck 帧携带的序号，由 RX 任务转交 TX 任务
+pub static HOST_ACK_CHANNEL: Channel<CriticalSectionRawMutex, u8, 4> = Channel::new();
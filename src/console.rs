@@ -0,0 +1,352 @@
+//! 文本命令控制台
+//!
+//! 用于台架调试：在串口终端里直接输入 `fan on`、`pump pulse 5000`、`get sensors` 等命令，
+//! 不必手算帧校验。命令行解析为与二进制帧相同的 `ControlCommand`，回复为可读文本。
+
+use crate::protocol::{
+    AckMode, ActuatorStates, ActuatorTag, CommandAck, ControlCommand, NackReason, SensorSnapshot,
+};
+use core::fmt::Write;
+
+/// 一行命令的最大长度
+pub const MAX_LINE_LEN: usize = 32;
+/// 一条回复的最大长度
+pub const MAX_REPLY_LEN: usize = 160;
+
+pub type ReplyText = heapless::String<MAX_REPLY_LEN>;
+
+/// 解析后的命令行
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Line {
+    Command(ControlCommand),
+    GetSensors,
+    GetActuators,
+    Help,
+    /// 退出控制台，恢复二进制上报
+    Exit,
+}
+
+/// 命令行错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LineError {
+    /// 未知命令
+    Unknown,
+    /// 参数缺失或不合法
+    BadArgument,
+    /// 超过 `MAX_LINE_LEN`
+    TooLong,
+}
+
+/// 发给 TX 任务的控制台回复，由 TX 任务格式化为文本
+#[derive(Debug, Clone, Copy)]
+pub enum Reply {
+    Help,
+    Ack(CommandAck),
+    Sensors(SensorSnapshot),
+    Actuators(ActuatorStates),
+    Error(LineError),
+    Bye,
+}
+
+/// 把收到的字节拼成命令行
+pub struct LineBuffer {
+    buf: [u8; MAX_LINE_LEN],
+    len: usize,
+    overflow: bool,
+}
+
+impl LineBuffer {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; MAX_LINE_LEN],
+            len: 0,
+            overflow: false,
+        }
+    }
+
+    /// 输入一个字节，遇到 CR / LF 时返回完整的一行 (空行忽略)
+    ///
+    /// 非可打印字符 (二进制帧的残片) 会清空当前行。
+    pub fn push(&mut self, byte: u8) -> Option<Result<&str, LineError>> {
+        match byte {
+            b'\r' | b'\n' => {
+                let len = core::mem::take(&mut self.len);
+                if core::mem::take(&mut self.overflow) {
+                    return Some(Err(LineError::TooLong));
+                }
+                // 只收可打印 ASCII，必然是合法 UTF-8
+                let line = core::str::from_utf8(&self.buf[..len]).ok()?.trim();
+                (!line.is_empty()).then_some(Ok(line))
+            }
+            0x20..=0x7E => {
+                if self.len < MAX_LINE_LEN {
+                    self.buf[self.len] = byte;
+                    self.len += 1;
+                } else {
+                    self.overflow = true;
+                }
+                None
+            }
+            _ => {
+                self.len = 0;
+                self.overflow = false;
+                None
+            }
+        }
+    }
+}
+
+impl Default for LineBuffer {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 解析一行命令 (不区分大小写)
+pub fn parse(line: &str) -> Result<Line, LineError> {
+    let mut words = line.split_ascii_whitespace();
+    let first = words.next().ok_or(LineError::Unknown)?;
+    let second = words.next();
+    let third = words.next();
+    if words.next().is_some() {
+        return Err(LineError::BadArgument);
+    }
+
+    if first.eq_ignore_ascii_case("help") || first == "?" {
+        return Ok(Line::Help);
+    }
+    if first.eq_ignore_ascii_case("exit") {
+        return Ok(Line::Exit);
+    }
+    if first.eq_ignore_ascii_case("get") {
+        return match (second, third) {
+            (Some(w), None) if w.eq_ignore_ascii_case("sensors") => Ok(Line::GetSensors),
+            (Some(w), None) if w.eq_ignore_ascii_case("actuators") => Ok(Line::GetActuators),
+            _ => Err(LineError::BadArgument),
+        };
+    }
+
+    let actuator = ActuatorTag::ALL
+        .into_iter()
+        .find(|&a| first.eq_ignore_ascii_case(actuator_name(a)))
+        .ok_or(LineError::Unknown)?;
+    let (state, duration_ms) = match (second, third) {
+        (Some(w), None) if w.eq_ignore_ascii_case("on") => (true, 0),
+        (Some(w), None) if w.eq_ignore_ascii_case("off") => (false, 0),
+        (Some(w), Some(ms)) if w.eq_ignore_ascii_case("pulse") => match ms.parse::<u16>() {
+            Ok(ms) if ms > 0 => (true, ms),
+            _ => return Err(LineError::BadArgument),
+        },
+        _ => return Err(LineError::BadArgument),
+    };
+    Ok(Line::Command(ControlCommand {
+        actuator,
+        state,
+        duration_ms,
+        seq: 0,
+        ack: AckMode::Text,
+    }))
+}
+
+/// 把回复格式化为文本，每行以 CRLF 结尾
+pub fn render(reply: &Reply) -> ReplyText {
+    let mut out = ReplyText::new();
+    // 缓冲区按最长的回复 (帮助) 估算，写满时截断即可
+    let _ = write_reply(&mut out, reply);
+    out
+}
+
+fn write_reply(out: &mut ReplyText, reply: &Reply) -> core::fmt::Result {
+    match reply {
+        Reply::Help => out.write_str(
+            "commands:\r\n  fan|pump|light|buzzer on|off\r\n  fan|pump|light|buzzer pulse <ms>\r\n  get sensors|actuators\r\n  exit\r\n",
+        ),
+        Reply::Ack(ack) => {
+            let name = ActuatorTag::try_from(ack.tag).map_or("?", actuator_name);
+            out.write_str(name)?;
+            if ack.success {
+                out.write_str(": ok\r\n")
+            } else {
+                out.write_str(": rejected (")?;
+                out.write_str(nack_name(ack.reason))?;
+                out.write_str(")\r\n")
+            }
+        }
+        Reply::Sensors(sensors) => {
+            out.write_str("soil=")?;
+            write_opt(out, sensors.soil_moisture, |out, v| write!(out, "{}", v))?;
+            out.write_str(" temp=")?;
+            write_opt(out, sensors.temperature, |out, v| {
+                if v < 0 {
+                    out.write_str("-")?;
+                }
+                let abs = v.unsigned_abs();
+                write!(out, "{}.{:02}C", abs / 100, abs % 100)
+            })?;
+            out.write_str(" hum=")?;
+            write_opt(out, sensors.humidity, |out, v| {
+                write!(out, "{}.{:02}%", v / 100, v % 100)
+            })?;
+            out.write_str(" light=")?;
+            write_opt(out, sensors.light_intensity, |out, v| write!(out, "{}lx", v))?;
+            out.write_str("\r\n")
+        }
+        Reply::Actuators(states) => {
+            for (i, actuator) in ActuatorTag::ALL.into_iter().enumerate() {
+                if i > 0 {
+                    out.write_str(" ")?;
+                }
+                out.write_str(actuator_name(actuator))?;
+                out.write_str(if states.get(actuator) { "=on" } else { "=off" })?;
+            }
+            out.write_str("\r\n")
+        }
+        Reply::Error(e) => out.write_str(match e {
+            LineError::Unknown => "error: unknown command, try 'help'\r\n",
+            LineError::BadArgument => "error: bad argument, try 'help'\r\n",
+            LineError::TooLong => "error: line too long\r\n",
+        }),
+        Reply::Bye => out.write_str("bye\r\n"),
+    }
+}
+
+/// 没有读数时输出 `-`
+fn write_opt<T>(
+    out: &mut ReplyText,
+    value: Option<T>,
+    f: impl FnOnce(&mut ReplyText, T) -> core::fmt::Result,
+) -> core::fmt::Result {
+    match value {
+        Some(v) => f(out, v),
+        None => out.write_str("-"),
+    }
+}
+
+fn actuator_name(actuator: ActuatorTag) -> &'static str {
+    match actuator {
+        ActuatorTag::Fan => "fan",
+        ActuatorTag::Pump => "pump",
+        ActuatorTag::Light => "light",
+        ActuatorTag::Buzzer => "buzzer",
+    }
+}
+
+fn nack_name(reason: NackReason) -> &'static str {
+    match reason {
+        NackReason::None => "none",
+        NackReason::UnknownTag => "unknown tag",
+        NackReason::BadLength => "bad length",
+        NackReason::Busy => "busy",
+        NackReason::Interlocked => "interlocked",
+        NackReason::OutOfRange => "out of range",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ActuatorFeedback;
+
+    fn command(actuator: ActuatorTag, state: bool, duration_ms: u16) -> Line {
+        Line::Command(ControlCommand {
+            actuator,
+            state,
+            duration_ms,
+            seq: 0,
+            ack: AckMode::Text,
+        })
+    }
+
+    #[test]
+    fn parse_commands() {
+        assert_eq!(parse("fan on"), Ok(command(ActuatorTag::Fan, true, 0)));
+        assert_eq!(
+            parse("Light  OFF"),
+            Ok(command(ActuatorTag::Light, false, 0))
+        );
+        assert_eq!(
+            parse("pump pulse 5000"),
+            Ok(command(ActuatorTag::Pump, true, 5000))
+        );
+        assert_eq!(parse("get sensors"), Ok(Line::GetSensors));
+        assert_eq!(parse("GET actuators"), Ok(Line::GetActuators));
+        assert_eq!(parse("help"), Ok(Line::Help));
+        assert_eq!(parse("?"), Ok(Line::Help));
+        assert_eq!(parse("exit"), Ok(Line::Exit));
+    }
+
+    #[test]
+    fn parse_errors() {
+        assert_eq!(parse("heater on"), Err(LineError::Unknown));
+        assert_eq!(parse("fan"), Err(LineError::BadArgument));
+        assert_eq!(parse("fan maybe"), Err(LineError::BadArgument));
+        assert_eq!(parse("pump pulse 0"), Err(LineError::BadArgument));
+        assert_eq!(parse("pump pulse 70000"), Err(LineError::BadArgument));
+        assert_eq!(parse("fan on now"), Err(LineError::BadArgument));
+        assert_eq!(parse("get weather"), Err(LineError::BadArgument));
+    }
+
+    #[test]
+    fn line_buffer_splits_lines() {
+        let mut lines = LineBuffer::new();
+        let mut got: heapless::Vec<heapless::String<MAX_LINE_LEN>, 4> = heapless::Vec::new();
+        for &b in b"fan on\r\n\r\n get sensors \n" {
+            if let Some(Ok(line)) = lines.push(b) {
+                got.push(line.try_into().unwrap()).unwrap();
+            }
+        }
+        assert_eq!(got.as_slice(), &["fan on", "get sensors"]);
+    }
+
+    #[test]
+    fn line_buffer_discards_binary_and_long_lines() {
+        let mut lines = LineBuffer::new();
+        // 二进制残片清空之前的内容
+        for &b in b"fa\xAAn" {
+            assert!(lines.push(b).is_none());
+        }
+        assert!(matches!(lines.push(b'\r'), Some(Ok("n"))));
+
+        for _ in 0..MAX_LINE_LEN + 1 {
+            assert!(lines.push(b'x').is_none());
+        }
+        assert!(matches!(lines.push(b'\n'), Some(Err(LineError::TooLong))));
+        // 之后恢复正常
+        for &b in b"help" {
+            lines.push(b);
+        }
+        assert!(matches!(lines.push(b'\n'), Some(Ok("help"))));
+    }
+
+    #[test]
+    fn render_replies() {
+        let mut sensors = SensorSnapshot::new();
+        sensors.temperature = Some(-250);
+        sensors.humidity = Some(4505);
+        sensors.light_intensity = Some(300);
+        assert_eq!(
+            render(&Reply::Sensors(sensors)).as_str(),
+            "soil=- temp=-2.50C hum=45.05% light=300lx\r\n"
+        );
+
+        let mut states = ActuatorStates::new();
+        states.update(ActuatorFeedback {
+            actuator: ActuatorTag::Pump,
+            state: true,
+            failsafe: false,
+            timestamp_ms: None,
+        });
+        assert_eq!(
+            render(&Reply::Actuators(states)).as_str(),
+            "fan=off pump=on light=off buzzer=off\r\n"
+        );
+
+        let ack = CommandAck::rejected(ActuatorTag::Fan as u8, NackReason::Interlocked, 0);
+        assert_eq!(
+            render(&Reply::Ack(ack)).as_str(),
+            "fan: rejected (interlocked)\r\n"
+        );
+        assert!(render(&Reply::Help).ends_with("exit\r\n"));
+    }
+}
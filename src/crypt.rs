@@ -0,0 +1,312 @@
+//! 串口链路加密 (ChaCha20-Poly1305, RFC 8439)
+//!
+//! 加密帧为版本字节带 `ENC_FLAG` 的 v2 帧，帧体为 `TYPE COUNTER(4) CIPHERTEXT TAG(16)`：
+//! TYPE 与帧头保持明文并作为附加认证数据 (AAD)，Payload 被加密。
+//! 会话密钥由预置密钥和下位机每次上电生成的随机数 (随 DeviceInfo 上报) 派生，
+//! 因此 COUNTER 只需在一次上电内不重复；两个方向使用不同的 nonce 前缀。
+
+use crate::auth::{AuthError, BOOT_NONCE_LEN, MAC_LEN, hmac_sha256};
+use crate::frame::{ENC_FLAG, crc16_ccitt, type_index};
+use chacha20poly1305::aead::AeadInPlace;
+use chacha20poly1305::{ChaCha20Poly1305, KeyInit};
+
+/// Poly1305 认证标签长度
+pub const TAG_LEN: usize = 16;
+/// 加密帧帧体比明文多出的长度: COUNTER(4) + TAG(16)
+pub const ENC_OVERHEAD: usize = 4 + TAG_LEN;
+/// 加密帧能携带的最长明文 Payload：LEN 字段只有一个字节，还要容纳 TYPE 和加密开销
+pub const MAX_PLAIN_LEN: usize = u8::MAX as usize - 1 - ENC_OVERHEAD;
+
+/// nonce 前缀，区分两个方向，避免同一计数器在两个方向上重用 nonce
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum Direction {
+    Uplink = 0x01,
+    Downlink = 0x02,
+}
+
+/// 由预置密钥和上电随机数派生会话密钥
+pub fn derive_key(secret: &[u8], boot_nonce: &[u8; BOOT_NONCE_LEN]) -> [u8; 32] {
+    let mut info = [0u8; 11 + BOOT_NONCE_LEN];
+    info[..11].copy_from_slice(b"iot-session");
+    info[11..].copy_from_slice(boot_nonce);
+    hmac_sha256(secret, &info)
+}
+
+/// 解密后的帧内容
+pub struct Opened<'b> {
+    pub counter: u32,
+    /// 截断的认证标签，用于重放判断
+    pub mac: [u8; MAC_LEN],
+    pub payload: &'b [u8],
+}
+
+/// 把一个已编码的明文 v2 帧就地加密，返回新的帧长度
+///
+/// `buffer[..len]` 必须是合法的 v2 帧，且尾部至少还有 `ENC_OVERHEAD` 字节空间。
+pub fn seal_frame(
+    key: &[u8; 32],
+    direction: Direction,
+    counter: u32,
+    buffer: &mut [u8],
+    len: usize,
+) -> usize {
+    // SOF VER [ADDR] SEQ LEN TYPE | PAYLOAD | CRC
+    let type_idx = type_index(buffer);
+    let payload_start = type_idx + 1;
+    let payload_len = len - payload_start - 2;
+    buffer[1] |= ENC_FLAG;
+    buffer[type_idx - 1] = (1 + ENC_OVERHEAD + payload_len) as u8;
+
+    // 明文后移 4 字节，给 COUNTER 腾出位置
+    let aad_len = payload_start + 4;
+    buffer.copy_within(payload_start..payload_start + payload_len, aad_len);
+    buffer[payload_start..aad_len].copy_from_slice(&counter.to_be_bytes());
+
+    let nonce = frame_nonce(direction, counter);
+    let (aad, rest) = buffer.split_at_mut(aad_len);
+    let tag = seal(key, &nonce, aad, &mut rest[..payload_len]);
+    let tag_idx = aad_len + payload_len;
+    buffer[tag_idx..tag_idx + TAG_LEN].copy_from_slice(&tag);
+
+    let crc_idx = tag_idx + TAG_LEN;
+    let crc = crc16_ccitt(&buffer[..crc_idx]);
+    buffer[crc_idx..crc_idx + 2].copy_from_slice(&crc.to_be_bytes());
+    crc_idx + 2
+}
+
+/// 解密一个已经通过 `check_frame` 校验的加密帧，明文 Payload 写入 `out`
+///
+/// `out` 至少要有 `MAX_PLAIN_LEN` 字节，否则过长的合法帧会被当作认证失败。
+pub fn open_frame<'b>(
+    key: &[u8; 32],
+    direction: Direction,
+    frame: &[u8],
+    out: &'b mut [u8],
+) -> Result<Opened<'b>, AuthError> {
+    let type_idx = type_index(frame);
+    let body_len = frame[type_idx - 1] as usize;
+    let Some(payload_len) = body_len.checked_sub(1 + ENC_OVERHEAD) else {
+        return Err(AuthError::BadMac);
+    };
+    if payload_len > out.len() {
+        return Err(AuthError::BadMac);
+    }
+
+    let c = type_idx + 1;
+    let counter = u32::from_be_bytes([frame[c], frame[c + 1], frame[c + 2], frame[c + 3]]);
+    let aad_len = c + 4;
+    let tag_idx = aad_len + payload_len;
+    let mut tag = [0u8; TAG_LEN];
+    tag.copy_from_slice(&frame[tag_idx..tag_idx + TAG_LEN]);
+
+    let plain = &mut out[..payload_len];
+    plain.copy_from_slice(&frame[aad_len..tag_idx]);
+    open(
+        key,
+        &frame_nonce(direction, counter),
+        &frame[..aad_len],
+        plain,
+        &tag,
+    )?;
+
+    let mut mac = [0u8; MAC_LEN];
+    mac.copy_from_slice(&tag[..MAC_LEN]);
+    Ok(Opened {
+        counter,
+        mac,
+        payload: plain,
+    })
+}
+
+fn frame_nonce(direction: Direction, counter: u32) -> [u8; 12] {
+    let mut nonce = [0u8; 12];
+    nonce[0] = direction as u8;
+    nonce[8..].copy_from_slice(&counter.to_be_bytes());
+    nonce
+}
+
+/// AEAD 加密，`data` 就地变为密文，返回认证标签
+pub fn seal(key: &[u8; 32], nonce: &[u8; 12], aad: &[u8], data: &mut [u8]) -> [u8; TAG_LEN] {
+    ChaCha20Poly1305::new(key.into())
+        .encrypt_in_place_detached(nonce.into(), aad, data)
+        // 只有明文超过 256 GiB 时才会失败
+        .unwrap()
+        .into()
+}
+
+/// AEAD 解密，标签不符时 `data` 保持为密文
+pub fn open(
+    key: &[u8; 32],
+    nonce: &[u8; 12],
+    aad: &[u8],
+    data: &mut [u8],
+    tag: &[u8; TAG_LEN],
+) -> Result<(), AuthError> {
+    ChaCha20Poly1305::new(key.into())
+        .decrypt_in_place_detached(nonce.into(), aad, data, tag.into())
+        .map_err(|_| AuthError::BadMac)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{
+        FrameError, FrameVersion, MAX_FRAME_LEN, check_frame, encode_msg, frame_parts, set_address,
+    };
+    use crate::protocol::{SensorData, SensorReading, TxMessage};
+
+    fn hex<const N: usize>(s: &str) -> heapless::Vec<u8, N> {
+        let s: heapless::String<512> = s.chars().filter(|c| !c.is_whitespace()).collect();
+        (0..s.len())
+            .step_by(2)
+            .map(|i| u8::from_str_radix(&s[i..i + 2], 16).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn aead_rfc8439() {
+        // RFC 8439 2.8.2
+        let key: [u8; 32] = core::array::from_fn(|i| 0x80 + i as u8);
+        let nonce = [
+            0x07, 0, 0, 0, 0x40, 0x41, 0x42, 0x43, 0x44, 0x45, 0x46, 0x47,
+        ];
+        let aad = hex::<12>("50515253c0c1c2c3c4c5c6c7");
+        let plain = b"Ladies and Gentlemen of the class of '99: If I could offer you only one tip for the future, sunscreen would be it.";
+        let mut data: heapless::Vec<u8, 128> = heapless::Vec::from_slice(plain).unwrap();
+
+        let tag = seal(&key, &nonce, &aad, &mut data);
+        assert_eq!(
+            data.as_slice(),
+            hex::<128>(
+                "d31a8d34648e60db7b86afbc53ef7ec2a4aded51296e08fea9e2b5a736ee62d6
+                 3dbea45e8ca9671282fafb69da92728b1a71de0a9e060b2905d6a5b67ecd3b36
+                 92ddbd7f2d778b8c9803aee328091b58fab324e4fad675945585808b4831d7bc
+                 3ff4def08e4b7a9de576d26586cec64b6116"
+            )
+            .as_slice()
+        );
+        assert_eq!(
+            tag.as_slice(),
+            hex::<16>("1ae10b594f09e26a7e902ecbd0600691").as_slice()
+        );
+
+        assert_eq!(open(&key, &nonce, &aad, &mut data, &tag), Ok(()));
+        assert_eq!(data.as_slice(), plain.as_slice());
+    }
+
+    #[test]
+    fn aead_rejects_tampering() {
+        let key = [0x11; 32];
+        let nonce = [0x22; 12];
+        let mut data = *b"pump on";
+        let tag = seal(&key, &nonce, b"hdr", &mut data);
+
+        let mut flipped = data;
+        flipped[0] ^= 1;
+        assert_eq!(
+            open(&key, &nonce, b"hdr", &mut flipped, &tag),
+            Err(AuthError::BadMac)
+        );
+        assert_eq!(
+            open(&key, &nonce, b"HDR", &mut data.clone(), &tag),
+            Err(AuthError::BadMac)
+        );
+    }
+
+    #[test]
+    fn frame_seal_open_round_trip() {
+        let key = derive_key(&[0x5A; 32], &[1, 2, 3, 4, 5, 6, 7, 8]);
+        let reading = SensorReading {
+            data: SensorData::Temperature(2500),
+            timestamp_ms: 0,
+        };
+        let mut buf = [0u8; 96];
+        let plain_len = encode_msg(
+            &TxMessage::Sensor(reading),
+            FrameVersion::V2,
+            3,
+            false,
+            &mut buf,
+        );
+        let plain_payload: heapless::Vec<u8, 16> =
+            heapless::Vec::from_slice(frame_parts(&buf[..plain_len]).payload).unwrap();
+
+        let len = seal_frame(&key, Direction::Uplink, 77, &mut buf, plain_len);
+        assert_eq!(len, plain_len + ENC_OVERHEAD);
+        assert!(matches!(check_frame(&buf[..len]), FrameError::Valid(l) if l == len));
+        let parts = frame_parts(&buf[..len]);
+        assert!(parts.encrypted);
+        assert_eq!(parts.msg_type, 0x01);
+        assert_eq!(parts.seq, 3);
+
+        let mut out = [0u8; 64];
+        let opened = open_frame(&key, Direction::Uplink, &buf[..len], &mut out)
+            .ok()
+            .unwrap();
+        assert_eq!(opened.counter, 77);
+        assert_eq!(opened.payload, plain_payload.as_slice());
+
+        // 方向不同 nonce 不同，无法解密
+        let mut out = [0u8; 64];
+        assert!(open_frame(&key, Direction::Downlink, &buf[..len], &mut out).is_err());
+        // 其它上电周期的密钥无法解密
+        let other = derive_key(&[0x5A; 32], &[1, 2, 3, 4, 5, 6, 7, 9]);
+        assert!(open_frame(&other, Direction::Uplink, &buf[..len], &mut out).is_err());
+    }
+
+    #[test]
+    fn addressed_frame_round_trip() {
+        let key = [0x33; 32];
+        let mut buf = [0u8; 96];
+        let len = encode_msg(
+            &TxMessage::Heartbeat { uptime_s: 60 },
+            FrameVersion::V2,
+            9,
+            false,
+            &mut buf,
+        );
+        let len = set_address(&mut buf, len, 0x21);
+        let len = seal_frame(&key, Direction::Downlink, 5, &mut buf, len);
+        assert!(matches!(check_frame(&buf[..len]), FrameError::Valid(l) if l == len));
+        let parts = frame_parts(&buf[..len]);
+        assert_eq!(
+            (parts.addr, parts.seq, parts.encrypted),
+            (Some(0x21), 9, true)
+        );
+
+        let mut out = [0u8; 16];
+        let opened = open_frame(&key, Direction::Downlink, &buf[..len], &mut out)
+            .ok()
+            .unwrap();
+        assert_eq!(opened.payload, &[0x01, 0x04, 0x00, 0x00, 0x00, 0x3C]);
+
+        // 地址属于 AAD，改地址 (并修正 CRC) 后无法解密
+        buf[2] = 0x22;
+        let crc = crc16_ccitt(&buf[..len - 2]);
+        buf[len - 2..len].copy_from_slice(&crc.to_be_bytes());
+        assert!(open_frame(&key, Direction::Downlink, &buf[..len], &mut out).is_err());
+    }
+
+    #[test]
+    fn longest_payload_round_trip() {
+        let key = [0x44; 32];
+        // 明文 Payload 最长时，加密后 LEN 正好是 255
+        let mut buf = [0u8; MAX_FRAME_LEN];
+        buf[..5].copy_from_slice(&[0xAA, 0x82, 1, (1 + MAX_PLAIN_LEN) as u8, 0x10]);
+        let crc_idx = 5 + MAX_PLAIN_LEN;
+        buf[5..crc_idx].fill(0x5C);
+        let crc = crc16_ccitt(&buf[..crc_idx]);
+        buf[crc_idx..crc_idx + 2].copy_from_slice(&crc.to_be_bytes());
+
+        let len = seal_frame(&key, Direction::Downlink, 1, &mut buf, crc_idx + 2);
+        assert_eq!(buf[3], u8::MAX);
+        assert!(matches!(check_frame(&buf[..len]), FrameError::Valid(l) if l == len));
+
+        let mut out = [0u8; MAX_PLAIN_LEN];
+        let opened = open_frame(&key, Direction::Downlink, &buf[..len], &mut out)
+            .ok()
+            .unwrap();
+        assert_eq!(opened.payload, &[0x5C; MAX_PLAIN_LEN]);
+    }
+}
@@ -0,0 +1,101 @@
+//! 变化上报 (死区)
+//!
+//! 设置了死区的传感器，只有读数偏离上一次上报值超过死区，或距上一次上报超过最长静默时间时才上报，
+//! 避免 ADC 噪声这类微小波动占用链路。未设置死区的传感器每次采样都上报。
+
+use crate::protocol::{Deadband, SensorData};
+
+/// 读数的数值 (原始单位)，用于比较变化量
+pub fn value(data: SensorData) -> i32 {
+    match data {
+        SensorData::SoilMoisture(v) | SensorData::Humidity(v) | SensorData::LightIntensity(v) => {
+            v.into()
+        }
+        SensorData::Temperature(v) => v.into(),
+    }
+}
+
+/// 记录一个传感器上一次上报的值和时刻
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ChangeFilter {
+    last: Option<(i32, u32)>,
+}
+
+impl ChangeFilter {
+    pub const fn new() -> Self {
+        Self { last: None }
+    }
+
+    /// 读数 `value` 在 `now_ms` 时刻是否应该上报 (第一次总是上报)
+    pub fn due(&self, value: i32, now_ms: u32, band: Option<Deadband>) -> bool {
+        let (Some(band), Some((last, at))) = (band, self.last) else {
+            return true;
+        };
+        let moved = value.abs_diff(last) > u32::from(band.threshold);
+        let silent = band.max_silence_ms != 0 && now_ms.wrapping_sub(at) >= band.max_silence_ms;
+        moved || silent
+    }
+
+    /// 记录一次上报
+    pub fn reported(&mut self, value: i32, now_ms: u32) {
+        self.last = Some((value, now_ms));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BAND: Option<Deadband> = Some(Deadband {
+        threshold: 20,
+        max_silence_ms: 60_000,
+    });
+
+    #[test]
+    fn without_deadband_always_due() {
+        let mut filter = ChangeFilter::new();
+        assert!(filter.due(100, 0, None));
+        filter.reported(100, 0);
+        assert!(filter.due(100, 1000, None));
+    }
+
+    #[test]
+    fn reports_only_when_value_leaves_band() {
+        let mut filter = ChangeFilter::new();
+        assert!(filter.due(3000, 0, BAND));
+        filter.reported(3000, 0);
+
+        // ADC 噪声在死区内
+        assert!(!filter.due(3015, 1000, BAND));
+        assert!(!filter.due(2980, 2000, BAND));
+        // 超过死区才上报，之后以新值为基准
+        assert!(filter.due(3021, 3000, BAND));
+        filter.reported(3021, 3000);
+        assert!(!filter.due(3001, 4000, BAND));
+        assert!(filter.due(3000, 5000, BAND));
+    }
+
+    #[test]
+    fn negative_values_compare_by_distance() {
+        let mut filter = ChangeFilter::new();
+        filter.reported(value(SensorData::Temperature(-10)), 0);
+        assert!(!filter.due(value(SensorData::Temperature(10)), 0, BAND));
+        assert!(filter.due(value(SensorData::Temperature(-31)), 0, BAND));
+    }
+
+    #[test]
+    fn max_silence_forces_report() {
+        let mut filter = ChangeFilter::new();
+        filter.reported(500, u32::MAX - 1000);
+        // 跨过 u32 毫秒计数回绕
+        assert!(!filter.due(500, 58_000, BAND));
+        assert!(filter.due(500, 59_000, BAND));
+
+        // 最长静默为 0 时只按变化上报
+        let band = Some(Deadband {
+            threshold: 20,
+            max_silence_ms: 0,
+        });
+        assert!(!filter.due(500, 1_000_000, band));
+    }
+}
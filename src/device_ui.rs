@@ -4,7 +4,6 @@ use embassy_executor::task;
 use embassy_stm32::gpio::Output;
 use embassy_stm32::mode::Async;
 use embassy_stm32::spi::Spi;
-use embassy_time; // Keep embassy_time for embassy_time::Delay
 use embedded_graphics::{
     mono_font::{MonoTextStyleBuilder, ascii::FONT_6X10},
     pixelcolor::Rgb565,
@@ -66,7 +65,7 @@ pub async fn ui_task(
         let msg = receiver.receive().await;
 
         match msg {
-            TxMessage::Sensor(data) => match data {
+            TxMessage::Sensor(reading) => match reading.data {
                 SensorData::Temperature(v) => {
                     if state.temp != Some(v) {
                         state.temp = Some(v);
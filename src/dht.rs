@@ -0,0 +1,307 @@
+//! DHT 系列温湿度传感器的数据解码
+//!
+//! DHT11、DHT22 和 AM2302 (DHT22 的带线封装) 使用相同的单总线时序和 5 字节帧
+//! (湿度 2 字节、温度 2 字节、校验和 1 字节)，但数据格式不同：
+//! DHT11 为整数字节加一位小数字节；DHT22/AM2302 为 0.1 单位的 16 位数，温度最高位为符号位。
+//!
+//! 驱动只记录单总线上每个边沿的时刻，由 `decode_pulses` 根据各电平的宽度还原出 40 位数据：
+//! 每一位先是约 50µs 的低电平，随后的高电平约 26µs 为 0，约 70µs 为 1。
+
+use core::ops::RangeInclusive;
+
+/// 传感器应答 (低、高电平各约 80µs) 之后是 40 个数据位，每位一段低电平和一段高电平
+pub const PULSES: usize = 2 + 40 * 2;
+
+/// 应答信号每段电平的合理宽度 (µs)
+const RESPONSE_US: RangeInclusive<u32> = 40..=120;
+/// 数据位起始低电平的合理宽度 (µs)
+const BIT_LOW_US: RangeInclusive<u32> = 30..=90;
+/// 数据位高电平的合理宽度 (µs)
+const BIT_HIGH_US: RangeInclusive<u32> = 10..=100;
+/// 高电平宽于此值 (µs) 为 1，取 0 (约 26µs) 与 1 (约 70µs) 的中点
+const ONE_THRESHOLD_US: u32 = 48;
+
+/// 传感器型号
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(dead_code)] // 只有 config 中选中的变体会被构造
+pub enum DhtVariant {
+    Dht11,
+    Dht22,
+    Am2302,
+}
+
+impl DhtVariant {
+    /// 两次读取的最小间隔 (ms)，读得更快时传感器返回的是上一次的测量值
+    pub const fn min_interval_ms(self) -> u32 {
+        match self {
+            DhtVariant::Dht11 => 1000,
+            DhtVariant::Dht22 | DhtVariant::Am2302 => 2000,
+        }
+    }
+
+    /// 起始信号的低电平时长 (ms)：DHT11 至少 18ms，DHT22/AM2302 为 1~10ms
+    pub const fn start_signal_ms(self) -> u64 {
+        match self {
+            DhtVariant::Dht11 => 20,
+            DhtVariant::Dht22 | DhtVariant::Am2302 => 2,
+        }
+    }
+
+    /// 测量范围内的温度 (0.01°C)
+    pub const fn temperature_range(self) -> RangeInclusive<i16> {
+        match self {
+            DhtVariant::Dht11 => 0..=5000,
+            DhtVariant::Dht22 | DhtVariant::Am2302 => -4000..=8000,
+        }
+    }
+
+    /// 测量范围内的湿度 (0.01%)
+    pub const fn humidity_range(self) -> RangeInclusive<u16> {
+        match self {
+            DhtVariant::Dht11 => 2000..=9000,
+            DhtVariant::Dht22 | DhtVariant::Am2302 => 0..=10000,
+        }
+    }
+}
+
+/// 一次测量结果，单位与 `SensorData` 相同，超出该型号量程的一项为 `None`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DhtReading {
+    /// 0.01°C
+    pub temperature: Option<i16>,
+    /// 0.01%
+    pub humidity: Option<u16>,
+}
+
+/// 读取或解码错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DhtError {
+    /// 传感器未应答，或未在规定时间内发完 40 位
+    Timeout,
+    /// 第 `n` 段电平 (从应答的低电平开始计) 的宽度不在预期范围内
+    Timing(u8),
+    /// 校验和 (前 4 字节之和的低 8 位) 不符
+    Checksum,
+    /// 温度和湿度都超出该型号的测量范围，多为接错型号或读到了干扰
+    OutOfRange,
+}
+
+/// 由各段电平的宽度 (µs) 还原出 5 字节
+///
+/// `widths_us` 从传感器应答的低电平开始，低、高电平交替，共 `PULSES` 段。
+pub fn decode_pulses(widths_us: &[u32; PULSES]) -> Result<[u8; 5], DhtError> {
+    let (response, bits) = widths_us.split_at(2);
+    for (i, width) in response.iter().enumerate() {
+        if !RESPONSE_US.contains(width) {
+            return Err(DhtError::Timing(i as u8));
+        }
+    }
+
+    let mut bytes = [0u8; 5];
+    for (bit, pair) in bits.chunks_exact(2).enumerate() {
+        let (low, high) = (pair[0], pair[1]);
+        if !BIT_LOW_US.contains(&low) {
+            return Err(DhtError::Timing((2 + bit * 2) as u8));
+        }
+        if !BIT_HIGH_US.contains(&high) {
+            return Err(DhtError::Timing((3 + bit * 2) as u8));
+        }
+        let byte = &mut bytes[bit / 8];
+        *byte = (*byte << 1) | u8::from(high > ONE_THRESHOLD_US);
+    }
+    Ok(bytes)
+}
+
+/// 按型号解码传感器发出的 5 字节
+///
+/// 温度和湿度分别按量程检查，只丢弃超出量程的一项：
+/// 例如 DHT11 在湿度高于 90% 时仍然给出有效的温度。
+pub fn decode(variant: DhtVariant, bytes: [u8; 5]) -> Result<DhtReading, DhtError> {
+    let sum = bytes[..4].iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    if sum != bytes[4] {
+        return Err(DhtError::Checksum);
+    }
+
+    let (humidity, temperature) = match variant {
+        // 整数部分加十分位
+        DhtVariant::Dht11 => (
+            u16::from(bytes[0]) * 100 + u16::from(bytes[1]) * 10,
+            i16::from(bytes[2]) * 100 + i16::from(bytes[3]) * 10,
+        ),
+        // 0.1 单位，温度为原码 (最高位为符号位，不是补码)
+        DhtVariant::Dht22 | DhtVariant::Am2302 => {
+            let humidity = u16::from_be_bytes([bytes[0], bytes[1]]);
+            let temperature = u16::from_be_bytes([bytes[2], bytes[3]]);
+            let magnitude = (temperature & 0x7FFF) as i16;
+            (
+                humidity.saturating_mul(10),
+                if temperature & 0x8000 != 0 {
+                    -magnitude.saturating_mul(10)
+                } else {
+                    magnitude.saturating_mul(10)
+                },
+            )
+        }
+    };
+
+    let reading = DhtReading {
+        temperature: Some(temperature).filter(|t| variant.temperature_range().contains(t)),
+        humidity: Some(humidity).filter(|h| variant.humidity_range().contains(h)),
+    };
+    if reading.temperature.is_none() && reading.humidity.is_none() {
+        return Err(DhtError::OutOfRange);
+    }
+    Ok(reading)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 按典型时序生成各段电平宽度 (µs)，`one_us` / `zero_us` 为 1 和 0 的高电平宽度
+    fn pulses(bytes: [u8; 5], zero_us: u32, one_us: u32) -> [u32; PULSES] {
+        let mut widths = [0; PULSES];
+        widths[0] = 80;
+        widths[1] = 80;
+        for bit in 0..40 {
+            let one = bytes[bit / 8] & (0x80 >> (bit % 8)) != 0;
+            widths[2 + bit * 2] = 50;
+            widths[3 + bit * 2] = if one { one_us } else { zero_us };
+        }
+        widths
+    }
+
+    /// 补上校验和
+    fn frame(data: [u8; 4]) -> [u8; 5] {
+        let sum = data.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+        [data[0], data[1], data[2], data[3], sum]
+    }
+
+    #[test]
+    fn dht11_integer_and_tenths() {
+        // 湿度 45%，温度 25.6°C
+        assert_eq!(
+            decode(DhtVariant::Dht11, frame([45, 0, 25, 6])),
+            Ok(DhtReading {
+                temperature: Some(2560),
+                humidity: Some(4500),
+            })
+        );
+    }
+
+    #[test]
+    fn dht22_tenths() {
+        // 湿度 65.2% (0x028C)，温度 35.1°C (0x015F)
+        assert_eq!(
+            decode(DhtVariant::Dht22, frame([0x02, 0x8C, 0x01, 0x5F])),
+            Ok(DhtReading {
+                temperature: Some(3510),
+                humidity: Some(6520),
+            })
+        );
+    }
+
+    #[test]
+    fn dht22_negative_temperature() {
+        // -10.1°C：符号位加 101 (0x0065)，不是补码
+        let reading = decode(DhtVariant::Am2302, frame([0x01, 0xF4, 0x80, 0x65])).unwrap();
+        assert_eq!(reading.temperature, Some(-1010));
+        assert_eq!(reading.humidity, Some(5000));
+
+        // -0.1°C
+        let reading = decode(DhtVariant::Dht22, frame([0x01, 0xF4, 0x80, 0x01])).unwrap();
+        assert_eq!(reading.temperature, Some(-10));
+
+        // 量程下限 -40.0°C
+        let reading = decode(DhtVariant::Dht22, frame([0x00, 0x00, 0x81, 0x90])).unwrap();
+        assert_eq!(reading.temperature, Some(-4000));
+    }
+
+    #[test]
+    fn pulses_classified_into_bits() {
+        let bytes = frame([0x01, 0xF4, 0x80, 0x65]);
+        assert_eq!(decode_pulses(&pulses(bytes, 26, 70)), Ok(bytes));
+        // 两端的容差：慢速的 0 和快速的 1 仍能区分
+        assert_eq!(decode_pulses(&pulses(bytes, 40, 55)), Ok(bytes));
+
+        // 端到端：DHT22 在零下的一帧
+        let reading = decode(
+            DhtVariant::Dht22,
+            decode_pulses(&pulses(bytes, 26, 70)).unwrap(),
+        );
+        assert_eq!(reading.unwrap().temperature, Some(-1010));
+    }
+
+    #[test]
+    fn bad_pulse_widths_rejected() {
+        let bytes = frame([45, 0, 25, 6]);
+
+        let mut widths = pulses(bytes, 26, 70);
+        widths[1] = 200;
+        assert_eq!(decode_pulses(&widths), Err(DhtError::Timing(1)));
+
+        // 第 3 位的高电平过长 (漏掉了一个边沿)
+        let mut widths = pulses(bytes, 26, 70);
+        widths[3 + 3 * 2] = 150;
+        assert_eq!(decode_pulses(&widths), Err(DhtError::Timing(9)));
+
+        // 干扰产生的毛刺
+        let mut widths = pulses(bytes, 26, 70);
+        widths[2 + 39 * 2] = 3;
+        assert_eq!(decode_pulses(&widths), Err(DhtError::Timing(80)));
+    }
+
+    #[test]
+    fn checksum_mismatch_rejected() {
+        let mut bytes = frame([0x02, 0x8C, 0x01, 0x5F]);
+        bytes[4] ^= 0x01;
+        assert_eq!(decode(DhtVariant::Dht22, bytes), Err(DhtError::Checksum));
+    }
+
+    #[test]
+    fn out_of_range_field_dropped() {
+        // -40.1°C 低于 DHT22 量程，湿度照常
+        assert_eq!(
+            decode(DhtVariant::Dht22, frame([0x01, 0xF4, 0x81, 0x91])),
+            Ok(DhtReading {
+                temperature: None,
+                humidity: Some(5000),
+            })
+        );
+        // 湿度 100.1%，温度照常
+        assert_eq!(
+            decode(DhtVariant::Dht22, frame([0x03, 0xE9, 0x00, 0xFA])),
+            Ok(DhtReading {
+                temperature: Some(2500),
+                humidity: None,
+            })
+        );
+        // DHT11 湿度 95% 超出量程 (20~90%)，温度 25.0°C 仍然有效
+        assert_eq!(
+            decode(DhtVariant::Dht11, frame([95, 0, 25, 0])),
+            Ok(DhtReading {
+                temperature: Some(2500),
+                humidity: None,
+            })
+        );
+        // 温度全 1 (原码 -3276.7°C) 也不会溢出
+        assert_eq!(
+            decode(DhtVariant::Am2302, frame([0x01, 0xF4, 0xFF, 0xFF])),
+            Ok(DhtReading {
+                temperature: None,
+                humidity: Some(5000),
+            })
+        );
+    }
+
+    #[test]
+    fn all_out_of_range_rejected() {
+        // 把 DHT22 的数据 (湿度 0%、-40.0°C) 按 DHT11 解码：湿度 0%、温度 143.4°C，都超出量程
+        assert_eq!(
+            decode(DhtVariant::Dht11, frame([0x00, 0x00, 0x81, 0x90])),
+            Err(DhtError::OutOfRange)
+        );
+    }
+}
@@ -0,0 +1,70 @@
+//! 链路诊断计数器
+//!
+//! 各任务直接累加全局原子计数器，便于在没有调试器的情况下排查现场问题。
+//! 上位机可通过 GetDiagnostics 读取 (并清零) 全部计数器。
+
+use crate::protocol::{DiagCounter, Diagnostics};
+use core::sync::atomic::{AtomicU32, Ordering};
+
+/// CRC 校验失败的帧数
+pub static RX_CRC_ERRORS: AtomicU32 = AtomicU32::new(0);
+/// 帧头非法、丢弃 SOF 重新同步的次数 (含收到一半超时的残帧)
+pub static RX_RESYNCS: AtomicU32 = AtomicU32::new(0);
+/// 帧头声明的长度超过接收缓冲区、跳过重新同步的次数
+pub static RX_OVERFLOWS: AtomicU32 = AtomicU32::new(0);
+/// UART 接收错误 (溢出、帧错误、噪声、奇偶校验)
+pub static RX_ERRORS: AtomicU32 = AtomicU32::new(0);
+/// UART 发送失败次数
+pub static TX_ERRORS: AtomicU32 = AtomicU32::new(0);
+
+/// 可靠上行帧的重传次数
+pub static TX_RETRANSMITS: AtomicU32 = AtomicU32::new(0);
+/// 可靠上行帧重传次数用尽仍未收到确认、被放弃的次数
+pub static TX_GIVE_UPS: AtomicU32 = AtomicU32::new(0);
+
+/// 认证失败 (伪造、重放或缺少认证) 被拒绝的帧数
+pub static AUTH_FAILURES: AtomicU32 = AtomicU32::new(0);
+
+/// UI 通道已满被丢弃的消息数
+pub static UI_DROPS: AtomicU32 = AtomicU32::new(0);
+/// 命令任务处理的控制命令总数
+pub static COMMANDS: AtomicU32 = AtomicU32::new(0);
+/// 传感器读数还没发出就被同一传感器的新读数覆盖的次数
+pub static SENSOR_OVERWRITES: AtomicU32 = AtomicU32::new(0);
+
+/// 计数器加一
+pub fn incr(counter: &AtomicU32) {
+    counter.fetch_add(1, Ordering::Relaxed);
+}
+
+fn counter(c: DiagCounter) -> &'static AtomicU32 {
+    match c {
+        DiagCounter::RxCrcErrors => &RX_CRC_ERRORS,
+        DiagCounter::RxResyncs => &RX_RESYNCS,
+        DiagCounter::RxOverflows => &RX_OVERFLOWS,
+        DiagCounter::RxErrors => &RX_ERRORS,
+        DiagCounter::TxErrors => &TX_ERRORS,
+        DiagCounter::TxRetransmits => &TX_RETRANSMITS,
+        DiagCounter::TxGiveUps => &TX_GIVE_UPS,
+        DiagCounter::AuthFailures => &AUTH_FAILURES,
+        DiagCounter::UiDrops => &UI_DROPS,
+        DiagCounter::Commands => &COMMANDS,
+        DiagCounter::SensorOverwrites => &SENSOR_OVERWRITES,
+    }
+}
+
+/// 读取全部计数器，`reset` 为 true 时读出后清零
+///
+/// 逐个原子交换，清零期间新增的计数不会丢失，只会计入下一次读数。
+pub fn snapshot(reset: bool) -> Diagnostics {
+    let mut diagnostics = Diagnostics::default();
+    for c in DiagCounter::ALL {
+        let value = if reset {
+            counter(c).swap(0, Ordering::Relaxed)
+        } else {
+            counter(c).load(Ordering::Relaxed)
+        };
+        diagnostics.counters[c.index()] = value;
+    }
+    diagnostics
+}
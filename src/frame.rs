@@ -0,0 +1,1893 @@
+//! 帧编解码模块
+//!
+//! 支持两种帧格式：
+//! - v1: `SOF LEN TYPE PAYLOAD XOR`
+//! - v2: `SOF VER SEQ LEN TYPE PAYLOAD CRC16_HI CRC16_LO`
+//!
+//! SOF 后的字节最高位为 1 时表示版本字节 (`0x80 | flags | version`)，因此 v1 的 LEN 不能超过 `V1_MAX_LEN`。
+//! 更长的 v1 帧仍按 v1 断帧 (LEN 恰好等于某个合法版本字节时除外，见通信协议文档)，
+//! 但 `decode_parts` 不接受：命令帧整帧回复 BadLength，其它消息丢弃。
+//! v1 帧没有序号字段，解析时序号视为 0。
+//!
+//! v2 版本字节带 `AUTH_FLAG` 时为认证帧，Payload 与 CRC 之间附加 `COUNTER(4) MAC(8)`，
+//! 该尾部不计入 LEN，校验见 `auth` 模块。
+//! 带 `ENC_FLAG` 时为加密帧，Payload 为密文，解密见 `crypt` 模块。两个标志不能同时出现。
+//!
+//! v2 版本字节带 `ADDR_FLAG` 时 VER 后附加一个节点地址字节 (`SOF VER ADDR SEQ LEN ...`)，
+//! 用于 RS-485 多机总线，可以与认证或加密标志同时出现。
+
+use crate::auth::AuthError;
+use crate::protocol::{
+    ACTUATOR_FLAG_FAILSAFE, AckMode, ActuatorFeedback, ActuatorTag, CommandAck, ConfigOption,
+    ConfigTag, ControlCommand, DEVICE_INFO_TAG_ACTUATORS, DEVICE_INFO_TAG_BOOT_NONCE,
+    DEVICE_INFO_TAG_FIRMWARE, DEVICE_INFO_TAG_PROTOCOL, DEVICE_INFO_TAG_RESET_CAUSE,
+    DEVICE_INFO_TAG_SENSORS, DEVICE_INFO_TAG_UID, DIAG_TAG_RESET, Deadband, DiagCounter,
+    Diagnostics, HEARTBEAT_TAG_UPTIME, MessageType, NackReason, ResetCause, RxMessage,
+    SECURITY_TAG_COUNTER, SECURITY_TAG_REASON, SOF, SensorData, SensorDeadband, SensorInterval,
+    SensorSnapshot, SensorStats, SensorTag, TAG_TIMESTAMP, TxMessage,
+};
+
+/// 最小帧长 SOF + LEN + TYPE + CRC (v1，Payload为0时)
+pub const MIN_FRAME_LEN: usize = 4;
+
+/// 版本字节标志位，SOF 后的字节带此标志时为版本字节
+pub const VERSION_FLAG: u8 = 0x80;
+/// v1 帧 LEN 的上限，更大的值与版本字节冲突
+pub const V1_MAX_LEN: u8 = VERSION_FLAG - 1;
+/// 版本字节中的认证标志 (仅 v2)
+pub const AUTH_FLAG: u8 = 0x40;
+/// 版本字节中的加密标志 (仅 v2)
+pub const ENC_FLAG: u8 = 0x20;
+/// 版本字节中的地址标志 (仅 v2)
+pub const ADDR_FLAG: u8 = 0x10;
+/// 广播地址，总线上所有节点都接收
+pub const BROADCAST_ADDR: u8 = 0xFF;
+/// 认证尾部长度: COUNTER(4) + MAC(8)
+pub const AUTH_TRAILER_LEN: usize = 12;
+/// 最长帧: SOF VER ADDR SEQ LEN + 255 字节 Body + 认证尾部 + CRC16
+pub const MAX_FRAME_LEN: usize = 5 + 255 + AUTH_TRAILER_LEN + 2;
+
+/// 帧格式版本
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum FrameVersion {
+    /// 无版本字节，XOR 校验
+    V1 = 0x01,
+    /// 带版本字节和序号，CRC-16/CCITT 校验
+    V2 = 0x02,
+}
+
+impl FrameVersion {
+    /// 本固件支持的最高版本，随 DeviceInfo 上报
+    pub const LATEST: FrameVersion = FrameVersion::V2;
+
+    /// SOF 到 TYPE 之前的头部长度
+    const fn header_len(self) -> usize {
+        match self {
+            FrameVersion::V1 => 2, // SOF LEN
+            FrameVersion::V2 => 4, // SOF VER SEQ LEN
+        }
+    }
+
+    /// 校验尾长度
+    const fn crc_len(self) -> usize {
+        match self {
+            FrameVersion::V1 => 1,
+            FrameVersion::V2 => 2,
+        }
+    }
+}
+
+/// 校验结果枚举
+pub enum FrameError {
+    HeaderError,  // 头不对
+    Incomplete,   // 数据没收完
+    CrcError,     // 校验失败
+    Valid(usize), // 合法帧，返回帧的总长度
+}
+
+/// 解码错误
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[allow(dead_code)] // 帧级错误只由 decode_frame 产生，下位机走 decode_parts
+pub enum DecodeError {
+    /// 帧头错误 (SOF 或 LEN 不合法)
+    Header,
+    /// 数据不足一帧
+    Incomplete,
+    /// 校验失败
+    Crc,
+    /// 未知的消息类型
+    UnknownType(u8),
+    /// Payload 与消息类型的格式不符
+    Malformed,
+    /// 加密帧，需要先用 `crypt::open_frame` 解密
+    Encrypted,
+}
+
+/// 已校验帧的各字段
+pub struct FrameParts<'a> {
+    pub version: FrameVersion,
+    /// 节点地址，不带地址的帧为 None
+    pub addr: Option<u8>,
+    pub seq: u8,
+    pub msg_type: u8,
+    pub payload: &'a [u8],
+    /// 认证帧的尾部，未认证的帧为 None
+    pub auth: Option<AuthTrailer<'a>>,
+    /// 加密帧，`payload` 为 `COUNTER CIPHERTEXT TAG`
+    pub encrypted: bool,
+}
+
+/// 认证帧尾部
+pub struct AuthTrailer<'a> {
+    pub counter: u32,
+    pub mac: &'a [u8],
+    /// MAC 覆盖的范围: SOF 到 COUNTER 结束
+    pub signed: &'a [u8],
+}
+
+/// 根据 SOF 后的第一个字节判断帧版本，以及帧标志 (`AUTH_FLAG` / `ENC_FLAG` / `ADDR_FLAG`)
+///
+/// 不是合法版本字节的值按 v1 的 LEN 处理 (超过 `V1_MAX_LEN` 的 v1 帧)。
+fn detect_version(byte: u8) -> (FrameVersion, u8) {
+    let flags = byte & (AUTH_FLAG | ENC_FLAG | ADDR_FLAG);
+    let exclusive = AUTH_FLAG | ENC_FLAG;
+    match byte & !flags {
+        0x82 if flags & exclusive != exclusive => (FrameVersion::V2, flags),
+        _ => (FrameVersion::V1, 0),
+    }
+}
+
+/// SOF 到 TYPE 之前的头部长度 (含地址字节)
+fn header_len(version: FrameVersion, flags: u8) -> usize {
+    version.header_len() + usize::from(flags & ADDR_FLAG != 0)
+}
+
+/// 已校验帧中 TYPE 字段的下标，即头部长度
+pub fn type_index(frame: &[u8]) -> usize {
+    let (version, flags) = detect_version(frame[1]);
+    header_len(version, flags)
+}
+
+/// 根据帧头计算整帧长度
+///
+/// 帧头还没收全时返回 `Incomplete`，SOF 或 LEN 不合法时返回 `HeaderError`。
+pub fn frame_len(data: &[u8]) -> Result<usize, FrameError> {
+    // 1. 基础长度检查
+    if data.len() < 2 {
+        return Err(FrameError::Incomplete);
+    }
+
+    // 2. 检查 SOF (帧头)
+    if data[0] != SOF {
+        return Err(FrameError::HeaderError);
+    }
+
+    // 3. 识别版本
+    let (version, flags) = detect_version(data[1]);
+    let header_len = header_len(version, flags);
+    if data.len() < header_len {
+        return Err(FrameError::Incomplete);
+    }
+
+    // 4. 计算理论上的总长度
+    // LEN 字段 = TYPE(1) + PAYLOAD(N)
+    // 总帧长 = 头部 + LEN的值 (+ 认证尾部) + CRC
+    let body_len = data[header_len - 1] as usize;
+    if body_len == 0 {
+        // 至少包含 TYPE
+        return Err(FrameError::HeaderError);
+    }
+    let trailer_len = if flags & AUTH_FLAG != 0 {
+        AUTH_TRAILER_LEN
+    } else {
+        0
+    };
+    Ok(header_len + body_len + trailer_len + version.crc_len())
+}
+
+/// 核心校验函数
+/// data: 收到的原始 buffer
+pub fn check_frame(data: &[u8]) -> FrameError {
+    if data.len() < MIN_FRAME_LEN {
+        return FrameError::Incomplete;
+    }
+    let expected_total_len = match frame_len(data) {
+        Ok(len) => len,
+        Err(e) => return e,
+    };
+    let (version, _) = detect_version(data[1]);
+
+    // 5. 检查是否接收完整
+    if data.len() < expected_total_len {
+        return FrameError::Incomplete;
+    }
+
+    // 6. 校验 CRC
+    // 范围：从 SOF 到 PAYLOAD 结束
+    let content_end = expected_total_len - version.crc_len();
+    let frame_content = &data[0..content_end];
+    let crc_ok = match version {
+        FrameVersion::V1 => calculate_crc(frame_content) == data[content_end],
+        FrameVersion::V2 => {
+            let received = u16::from_be_bytes([data[content_end], data[content_end + 1]]);
+            crc16_ccitt(frame_content) == received
+        }
+    };
+
+    if !crc_ok {
+        return FrameError::CrcError;
+    }
+
+    FrameError::Valid(expected_total_len)
+}
+
+/// 拆分一个已经通过 `check_frame` 校验的完整帧
+pub fn frame_parts(frame: &[u8]) -> FrameParts<'_> {
+    let (version, flags) = detect_version(frame[1]);
+    let header_len = header_len(version, flags);
+    let body_len = frame[header_len - 1] as usize;
+    let type_idx = header_len;
+    let body_end = header_len + body_len;
+
+    FrameParts {
+        version,
+        addr: (flags & ADDR_FLAG != 0).then(|| frame[2]),
+        seq: match version {
+            FrameVersion::V1 => 0,
+            FrameVersion::V2 => frame[header_len - 2],
+        },
+        msg_type: frame[type_idx],
+        payload: &frame[type_idx + 1..body_end],
+        encrypted: flags & ENC_FLAG != 0,
+        auth: (flags & AUTH_FLAG != 0).then(|| {
+            let mac_start = body_end + 4;
+            AuthTrailer {
+                counter: u32::from_be_bytes([
+                    frame[body_end],
+                    frame[body_end + 1],
+                    frame[body_end + 2],
+                    frame[body_end + 3],
+                ]),
+                mac: &frame[mac_start..mac_start + AUTH_TRAILER_LEN - 4],
+                signed: &frame[..mac_start],
+            }
+        }),
+    }
+}
+
+/// 校验并解码一个完整帧
+///
+/// 下位机接收任务需要先认证/解密，直接调用 `decode_parts`；此函数供主机测试和上位机使用。
+#[allow(dead_code)]
+pub fn decode_frame(data: &[u8]) -> Result<RxMessage, DecodeError> {
+    match check_frame(data) {
+        FrameError::Valid(_) => {}
+        FrameError::HeaderError => return Err(DecodeError::Header),
+        FrameError::Incomplete => return Err(DecodeError::Incomplete),
+        FrameError::CrcError => return Err(DecodeError::Crc),
+    }
+
+    let parts = frame_parts(data);
+    if parts.encrypted {
+        return Err(DecodeError::Encrypted);
+    }
+    decode_parts(&parts)
+}
+
+/// 解码已拆分 (或已解密) 的帧
+pub fn decode_parts(parts: &FrameParts) -> Result<RxMessage, DecodeError> {
+    let payload = parts.payload;
+    let msg_type = MessageType::from(parts.msg_type);
+
+    // LEN 超过 V1_MAX_LEN 的 v1 帧不接受：命令帧按第一个 TAG 整帧回复 BadLength
+    if parts.version == FrameVersion::V1 && payload.len() >= usize::from(V1_MAX_LEN) {
+        let (MessageType::Command, Some(&tag)) = (msg_type, payload.first()) else {
+            return Err(DecodeError::Malformed);
+        };
+        let mut commands = heapless::Vec::new();
+        let _ = commands.push(Err(CommandAck::rejected(tag, NackReason::BadLength, 0)));
+        return Ok(RxMessage::Command { seq: 0, commands });
+    }
+
+    match msg_type {
+        MessageType::SensorReport => {
+            let mut snapshot = SensorSnapshot::default();
+            // 最近一条读数，时间戳 TLV 属于它
+            let mut last: Option<SensorTag> = None;
+            for tlv in Tlvs::new(payload) {
+                let (tag, value) = tlv?;
+                if tag == TAG_TIMESTAMP {
+                    let index = last.take().ok_or(DecodeError::Malformed)?.index();
+                    snapshot.timestamps_ms[index] = Some(be_u32(value)?);
+                    continue;
+                }
+                // 未知的传感器 TAG 跳过，便于以后扩展
+                last = None;
+                if let Some(data) = sensor_data(tag, value)? {
+                    snapshot.update(data);
+                    last = Some(data.tag());
+                }
+            }
+            Ok(RxMessage::SensorReport(snapshot))
+        }
+        MessageType::ActuatorStatus => {
+            // 每个 TLV: [TAG] [LEN=1] [STATE] 或 [TAG] [LEN=2] [STATE] [FLAGS]
+            let mut feedbacks = heapless::Vec::new();
+            for tlv in Tlvs::new(payload) {
+                let (tag, value) = tlv?;
+                if tag == TAG_TIMESTAMP {
+                    let last: &mut ActuatorFeedback =
+                        feedbacks.last_mut().ok_or(DecodeError::Malformed)?;
+                    if last.timestamp_ms.is_some() {
+                        return Err(DecodeError::Malformed);
+                    }
+                    last.timestamp_ms = Some(be_u32(value)?);
+                    continue;
+                }
+                let (state, flags) = match *value {
+                    [state] => (state, 0),
+                    [state, flags] => (state, flags),
+                    _ => return Err(DecodeError::Malformed),
+                };
+                let feedback = ActuatorFeedback {
+                    actuator: ActuatorTag::try_from(tag).map_err(|_| DecodeError::Malformed)?,
+                    state: state != 0,
+                    failsafe: flags & ACTUATOR_FLAG_FAILSAFE != 0,
+                    timestamp_ms: None,
+                };
+                feedbacks
+                    .push(feedback)
+                    .map_err(|_| DecodeError::Malformed)?;
+            }
+            Ok(RxMessage::ActuatorStatus(feedbacks))
+        }
+        MessageType::Command => {
+            let mut commands = heapless::Vec::new();
+            for tlv in Tlvs::new(payload) {
+                let (tag, value) = tlv?;
+                commands
+                    .push(decode_command(tag, value, parts.seq))
+                    .map_err(|_| DecodeError::Malformed)?;
+            }
+            Ok(RxMessage::Command {
+                seq: parts.seq,
+                commands,
+            })
+        }
+        MessageType::CommandAck => {
+            // 成功: [TAG] [1] [0x01]；失败: v1 为 [TAG] [1] [0x00]，v2 为 [TAG] [2] [0x00] [REASON]
+            let ack = match payload {
+                [tag, 1, 0x01] => CommandAck {
+                    tag: *tag,
+                    success: true,
+                    reason: NackReason::None,
+                    seq: parts.seq,
+                },
+                [tag, 1, 0x00] => CommandAck::rejected(*tag, NackReason::None, parts.seq),
+                [tag, 2, 0x00, reason] => CommandAck::rejected(
+                    *tag,
+                    NackReason::try_from(*reason).map_err(|_| DecodeError::Malformed)?,
+                    parts.seq,
+                ),
+                _ => return Err(DecodeError::Malformed),
+            };
+            Ok(RxMessage::CommandAck(ack))
+        }
+        MessageType::Configure => {
+            let mut options = heapless::Vec::new();
+            for tlv in Tlvs::new(payload) {
+                let (tag, value) = tlv?;
+                options
+                    .push(decode_config(tag, value, parts.seq))
+                    .map_err(|_| DecodeError::Malformed)?;
+            }
+            Ok(RxMessage::Configure {
+                seq: parts.seq,
+                options,
+            })
+        }
+        MessageType::SetInterval => {
+            let mut intervals = heapless::Vec::new();
+            for tlv in Tlvs::new(payload) {
+                let (tag, value) = tlv?;
+                intervals
+                    .push(decode_interval(tag, value, parts.seq))
+                    .map_err(|_| DecodeError::Malformed)?;
+            }
+            Ok(RxMessage::SetInterval {
+                seq: parts.seq,
+                intervals,
+            })
+        }
+        MessageType::SetDeadband => {
+            let mut deadbands = heapless::Vec::new();
+            for tlv in Tlvs::new(payload) {
+                let (tag, value) = tlv?;
+                deadbands
+                    .push(decode_deadband(tag, value, parts.seq))
+                    .map_err(|_| DecodeError::Malformed)?;
+            }
+            Ok(RxMessage::SetDeadband {
+                seq: parts.seq,
+                deadbands,
+            })
+        }
+        MessageType::HostAck => Ok(RxMessage::HostAck { seq: parts.seq }),
+        MessageType::Heartbeat => {
+            let mut uptime_s = None;
+            for tlv in Tlvs::new(payload) {
+                if let (HEARTBEAT_TAG_UPTIME, &[a, b, c, d]) = tlv? {
+                    uptime_s = Some(u32::from_be_bytes([a, b, c, d]));
+                }
+            }
+            Ok(RxMessage::Heartbeat { uptime_s })
+        }
+        MessageType::SecurityEvent => {
+            let mut reason = None;
+            let mut counter = None;
+            for tlv in Tlvs::new(payload) {
+                match tlv? {
+                    (SECURITY_TAG_REASON, &[r]) => {
+                        reason = Some(AuthError::try_from(r).map_err(|_| DecodeError::Malformed)?);
+                    }
+                    (SECURITY_TAG_COUNTER, value) => counter = Some(be_u32(value)?),
+                    _ => {}
+                }
+            }
+            Ok(RxMessage::SecurityEvent {
+                reason: reason.ok_or(DecodeError::Malformed)?,
+                counter,
+            })
+        }
+        MessageType::GetSensors => Ok(RxMessage::GetSensors),
+        MessageType::GetActuators => Ok(RxMessage::GetActuators),
+        MessageType::GetDeviceInfo => Ok(RxMessage::GetDeviceInfo),
+        MessageType::DeviceInfo => {
+            // 缺少的字段取默认值，未知 TAG 跳过，便于以后扩展
+            let mut firmware = [0u8; 3];
+            let mut protocol = 0;
+            let mut uid = [0u8; 12];
+            let mut reset_cause = ResetCause::Unknown;
+            let mut sensors = heapless::Vec::new();
+            let mut actuators = heapless::Vec::new();
+            let mut boot_nonce = [0u8; 8];
+            for tlv in Tlvs::new(payload) {
+                match tlv? {
+                    (DEVICE_INFO_TAG_FIRMWARE, value) => {
+                        firmware = value.try_into().map_err(|_| DecodeError::Malformed)?;
+                    }
+                    (DEVICE_INFO_TAG_PROTOCOL, &[version]) => protocol = version,
+                    (DEVICE_INFO_TAG_UID, value) => {
+                        uid = value.try_into().map_err(|_| DecodeError::Malformed)?;
+                    }
+                    (DEVICE_INFO_TAG_RESET_CAUSE, &[cause]) => reset_cause = cause.into(),
+                    (DEVICE_INFO_TAG_SENSORS, tags) => {
+                        for tag in tags.iter().filter_map(|&t| SensorTag::try_from(t).ok()) {
+                            sensors.push(tag).map_err(|_| DecodeError::Malformed)?;
+                        }
+                    }
+                    (DEVICE_INFO_TAG_ACTUATORS, tags) => {
+                        for tag in tags.iter().filter_map(|&t| ActuatorTag::try_from(t).ok()) {
+                            actuators.push(tag).map_err(|_| DecodeError::Malformed)?;
+                        }
+                    }
+                    (DEVICE_INFO_TAG_BOOT_NONCE, value) => {
+                        boot_nonce = value.try_into().map_err(|_| DecodeError::Malformed)?;
+                    }
+                    (DEVICE_INFO_TAG_PROTOCOL | DEVICE_INFO_TAG_RESET_CAUSE, _) => {
+                        return Err(DecodeError::Malformed);
+                    }
+                    _ => {}
+                }
+            }
+            Ok(RxMessage::DeviceInfo {
+                firmware,
+                protocol,
+                uid,
+                reset_cause,
+                sensors,
+                actuators,
+                boot_nonce,
+            })
+        }
+        MessageType::SensorStats => {
+            let mut stats = [None; SensorTag::ALL.len()];
+            for tlv in Tlvs::new(payload) {
+                let (tag, value) = tlv?;
+                // 未知的传感器 TAG 跳过，便于以后扩展
+                let Ok(sensor) = SensorTag::try_from(tag) else {
+                    continue;
+                };
+                if value.len() != 10 {
+                    return Err(DecodeError::Malformed);
+                }
+                let field = |i: usize| stat_value(sensor, [value[i], value[i + 1]]);
+                stats[sensor.index()] = Some(SensorStats {
+                    sensor,
+                    min: field(0),
+                    max: field(2),
+                    mean: field(4),
+                    count: be_u32(&value[6..])?,
+                });
+            }
+            Ok(RxMessage::SensorStats(stats))
+        }
+        MessageType::Diagnostics => {
+            // 未知计数器跳过，便于以后扩展
+            let mut diagnostics = Diagnostics::default();
+            for tlv in Tlvs::new(payload) {
+                let (tag, value) = tlv?;
+                if let Ok(counter) = DiagCounter::try_from(tag) {
+                    diagnostics.counters[counter.index()] = be_u32(value)?;
+                }
+            }
+            Ok(RxMessage::Diagnostics(diagnostics))
+        }
+        MessageType::GetDiagnostics => {
+            let mut reset = false;
+            for tlv in Tlvs::new(payload) {
+                match tlv? {
+                    (DIAG_TAG_RESET, &[flag]) => reset = flag != 0,
+                    (DIAG_TAG_RESET, _) => return Err(DecodeError::Malformed),
+                    _ => {}
+                }
+            }
+            Ok(RxMessage::GetDiagnostics { reset })
+        }
+        MessageType::Unknown => Err(DecodeError::UnknownType(parts.msg_type)),
+    }
+}
+
+/// Payload 中的 TLV 迭代器
+struct Tlvs<'a> {
+    payload: &'a [u8],
+}
+
+impl<'a> Tlvs<'a> {
+    fn new(payload: &'a [u8]) -> Self {
+        Self { payload }
+    }
+}
+
+impl<'a> Iterator for Tlvs<'a> {
+    type Item = Result<(u8, &'a [u8]), DecodeError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.payload.is_empty() {
+            return None;
+        }
+        // Need at least Tag + Len
+        if self.payload.len() < 2 {
+            self.payload = &[];
+            return Some(Err(DecodeError::Malformed));
+        }
+        let tag = self.payload[0];
+        let val_end = 2 + self.payload[1] as usize;
+        if val_end > self.payload.len() {
+            self.payload = &[];
+            return Some(Err(DecodeError::Malformed));
+        }
+        let value = &self.payload[2..val_end];
+        self.payload = &self.payload[val_end..];
+        Some(Ok((tag, value)))
+    }
+}
+
+/// 解析一条命令 TLV，不合法时返回对应的 NACK
+fn decode_command(tag: u8, value: &[u8], seq: u8) -> Result<ControlCommand, CommandAck> {
+    let actuator = ActuatorTag::try_from(tag)
+        .map_err(|tag| CommandAck::rejected(tag, NackReason::UnknownTag, seq))?;
+
+    // Len == 1 => State (0/1)
+    // Len == 2 => Duration (u16)，隐含开启
+    let (state, duration_ms) = match value {
+        [state] => (*state != 0, 0),
+        [hi, lo] => (true, u16::from_be_bytes([*hi, *lo])),
+        _ => return Err(CommandAck::rejected(tag, NackReason::BadLength, seq)),
+    };
+
+    Ok(ControlCommand {
+        actuator,
+        state,
+        duration_ms,
+        seq,
+        ack: AckMode::Frame,
+    })
+}
+
+/// 解码一条选项设置，失败时返回对应的 NACK
+fn decode_config(tag: u8, value: &[u8], seq: u8) -> Result<ConfigOption, CommandAck> {
+    let config = ConfigTag::try_from(tag)
+        .map_err(|tag| CommandAck::rejected(tag, NackReason::UnknownTag, seq))?;
+
+    match (config, value) {
+        (ConfigTag::Timestamps, [enable]) => Ok(ConfigOption::Timestamps(*enable != 0)),
+        (ConfigTag::StatsWindow, [a, b, c, d]) => {
+            Ok(ConfigOption::StatsWindow(u32::from_be_bytes([
+                *a, *b, *c, *d,
+            ])))
+        }
+        _ => Err(CommandAck::rejected(tag, NackReason::BadLength, seq)),
+    }
+}
+
+/// 采样间隔 TLV: `[SensorTag] [4] [INTERVAL_MS (u32)]`
+fn decode_interval(tag: u8, value: &[u8], seq: u8) -> Result<SensorInterval, CommandAck> {
+    let sensor = SensorTag::try_from(tag)
+        .map_err(|tag| CommandAck::rejected(tag, NackReason::UnknownTag, seq))?;
+    let interval_ms =
+        be_u32(value).map_err(|_| CommandAck::rejected(tag, NackReason::BadLength, seq))?;
+    Ok(SensorInterval {
+        sensor,
+        interval_ms,
+    })
+}
+
+/// 死区 TLV: `[SensorTag] [6] [THRESHOLD (u16)] [MAX_SILENCE_MS (u32)]`，LEN 为 0 时取消死区
+fn decode_deadband(tag: u8, value: &[u8], seq: u8) -> Result<SensorDeadband, CommandAck> {
+    let sensor = SensorTag::try_from(tag)
+        .map_err(|tag| CommandAck::rejected(tag, NackReason::UnknownTag, seq))?;
+    let band = match value {
+        [] => None,
+        [t0, t1, s0, s1, s2, s3] => Some(Deadband {
+            threshold: u16::from_be_bytes([*t0, *t1]),
+            max_silence_ms: u32::from_be_bytes([*s0, *s1, *s2, *s3]),
+        }),
+        _ => return Err(CommandAck::rejected(tag, NackReason::BadLength, seq)),
+    };
+    Ok(SensorDeadband { sensor, band })
+}
+
+/// 4 字节大端 u32 的 TLV 值 (时间戳、计数器)
+fn be_u32(value: &[u8]) -> Result<u32, DecodeError> {
+    value
+        .try_into()
+        .map(u32::from_be_bytes)
+        .map_err(|_| DecodeError::Malformed)
+}
+
+/// 传感器 TLV 转为读数，未知 TAG 返回 None
+fn sensor_data(tag: u8, value: &[u8]) -> Result<Option<SensorData>, DecodeError> {
+    let Ok(tag) = SensorTag::try_from(tag) else {
+        return Ok(None);
+    };
+    let [hi, lo] = value else {
+        return Err(DecodeError::Malformed);
+    };
+    let raw = u16::from_be_bytes([*hi, *lo]);
+    Ok(Some(match tag {
+        SensorTag::SoilMoisture => SensorData::SoilMoisture(raw),
+        SensorTag::Temperature => SensorData::Temperature(raw as i16),
+        SensorTag::Humidity => SensorData::Humidity(raw),
+        SensorTag::LightIntensity => SensorData::LightIntensity(raw),
+    }))
+}
+
+/// 统计值与传感器读数同宽：温度为 i16，其余为 u16
+fn stat_value(sensor: SensorTag, raw: [u8; 2]) -> i32 {
+    let raw = u16::from_be_bytes(raw);
+    match sensor {
+        SensorTag::Temperature => (raw as i16).into(),
+        _ => raw.into(),
+    }
+}
+
+/// 简单的异或校验 (XOR Checksum)，用于 v1 帧
+pub fn calculate_crc(data: &[u8]) -> u8 {
+    let mut crc = 0;
+    for &byte in data {
+        crc ^= byte;
+    }
+    crc
+}
+
+/// CRC-16/CCITT-FALSE (poly 0x1021, init 0xFFFF)，用于 v2 帧
+pub fn crc16_ccitt(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            if crc & 0x8000 != 0 {
+                crc = (crc << 1) ^ 0x1021;
+            } else {
+                crc <<= 1;
+            }
+        }
+    }
+    crc
+}
+
+/// 将消息编码为指定版本的帧，返回帧总长度
+///
+/// `seq` 为上行帧序号 (仅 v2 有效)；`CommandAck` 帧使用其回显的命令序号。
+/// `timestamps` 为 true 时在每条传感器/执行器 TLV 后附加时间戳 TLV。
+pub fn encode_msg(
+    msg: &TxMessage,
+    version: FrameVersion,
+    seq: u8,
+    timestamps: bool,
+    buffer: &mut [u8],
+) -> usize {
+    // 构造 Payload
+    // v1 Frame: SOF, LEN, TYPE, Payload..., CRC
+    // v2 Frame: SOF, VER, SEQ, LEN, TYPE, Payload..., CRC_HI, CRC_LO
+
+    let header_len = version.header_len();
+    buffer[0] = SOF;
+    if version == FrameVersion::V2 {
+        buffer[1] = VERSION_FLAG | version as u8;
+        buffer[2] = match msg {
+            TxMessage::Ack(ack) => ack.seq,
+            _ => seq,
+        };
+    }
+    // buffer[header_len - 1] = LEN (filled later)
+    // buffer[header_len] = TYPE (filled later)
+
+    let payload_start = header_len + 1;
+    let mut payload_idx = payload_start;
+    let msg_type;
+
+    match msg {
+        TxMessage::Sensor(reading) => {
+            msg_type = MessageType::SensorReport;
+            append_sensor_tlv(buffer, &mut payload_idx, &reading.data);
+            if timestamps {
+                append_timestamp_tlv(buffer, &mut payload_idx, Some(reading.timestamp_ms));
+            }
+        }
+        TxMessage::Snapshot(snapshot) => {
+            msg_type = MessageType::SensorReport;
+            for data in snapshot.readings() {
+                append_sensor_tlv(buffer, &mut payload_idx, &data);
+                if timestamps {
+                    let ts = snapshot.timestamps_ms[data.tag().index()];
+                    append_timestamp_tlv(buffer, &mut payload_idx, ts);
+                }
+            }
+        }
+        TxMessage::Stats(stats) => {
+            msg_type = MessageType::SensorStats;
+            for s in stats.iter().flatten() {
+                // MIN MAX MEAN 与传感器读数同宽 (i16 或 u16)，截断即得补码
+                let mut value = [0u8; 10];
+                value[0..2].copy_from_slice(&(s.min as u16).to_be_bytes());
+                value[2..4].copy_from_slice(&(s.max as u16).to_be_bytes());
+                value[4..6].copy_from_slice(&(s.mean as u16).to_be_bytes());
+                value[6..10].copy_from_slice(&s.count.to_be_bytes());
+                append_tlv_bytes(buffer, &mut payload_idx, s.sensor as u8, &value);
+            }
+        }
+        TxMessage::Actuator(status) => {
+            msg_type = MessageType::ActuatorStatus;
+            append_actuator_tlv(buffer, &mut payload_idx, status);
+            if timestamps {
+                append_timestamp_tlv(buffer, &mut payload_idx, status.timestamp_ms);
+            }
+        }
+        TxMessage::ActuatorStates(states) => {
+            msg_type = MessageType::ActuatorStatus;
+            for status in states.feedbacks() {
+                append_actuator_tlv(buffer, &mut payload_idx, &status);
+                if timestamps {
+                    append_timestamp_tlv(buffer, &mut payload_idx, status.timestamp_ms);
+                }
+            }
+        }
+        TxMessage::Ack(ack) => {
+            msg_type = MessageType::CommandAck;
+            // Tag
+            buffer[payload_idx] = ack.tag;
+            payload_idx += 1;
+            if ack.success {
+                // Len + Value (0x01 Success)
+                buffer[payload_idx] = 1;
+                buffer[payload_idx + 1] = 1;
+                payload_idx += 2;
+            } else if version == FrameVersion::V1 {
+                // v1 格式不变: Len + Value (0x00 Fail)，不带原因
+                buffer[payload_idx] = 1;
+                buffer[payload_idx + 1] = 0;
+                payload_idx += 2;
+            } else {
+                // Len + Value (0x00 Fail) + Reason
+                buffer[payload_idx] = 2;
+                buffer[payload_idx + 1] = 0;
+                buffer[payload_idx + 2] = ack.reason as u8;
+                payload_idx += 3;
+            }
+        }
+        TxMessage::Heartbeat { uptime_s } => {
+            msg_type = MessageType::Heartbeat;
+            buffer[payload_idx] = HEARTBEAT_TAG_UPTIME;
+            buffer[payload_idx + 1] = 4;
+            buffer[payload_idx + 2..payload_idx + 6].copy_from_slice(&uptime_s.to_be_bytes());
+            payload_idx += 6;
+        }
+        TxMessage::SecurityEvent { reason, counter } => {
+            msg_type = MessageType::SecurityEvent;
+            append_tlv_bytes(
+                buffer,
+                &mut payload_idx,
+                SECURITY_TAG_REASON,
+                &[*reason as u8],
+            );
+            if let Some(counter) = counter {
+                append_tlv_bytes(
+                    buffer,
+                    &mut payload_idx,
+                    SECURITY_TAG_COUNTER,
+                    &counter.to_be_bytes(),
+                );
+            }
+        }
+        TxMessage::DeviceInfo(info) => {
+            msg_type = MessageType::DeviceInfo;
+            append_tlv_bytes(
+                buffer,
+                &mut payload_idx,
+                DEVICE_INFO_TAG_FIRMWARE,
+                &info.firmware,
+            );
+            append_tlv_bytes(
+                buffer,
+                &mut payload_idx,
+                DEVICE_INFO_TAG_PROTOCOL,
+                &[info.protocol],
+            );
+            append_tlv_bytes(buffer, &mut payload_idx, DEVICE_INFO_TAG_UID, &info.uid);
+            append_tlv_bytes(
+                buffer,
+                &mut payload_idx,
+                DEVICE_INFO_TAG_RESET_CAUSE,
+                &[info.reset_cause as u8],
+            );
+            append_tlv_tags(
+                buffer,
+                &mut payload_idx,
+                DEVICE_INFO_TAG_SENSORS,
+                info.sensors.iter().map(|&t| t as u8),
+            );
+            append_tlv_tags(
+                buffer,
+                &mut payload_idx,
+                DEVICE_INFO_TAG_ACTUATORS,
+                info.actuators.iter().map(|&t| t as u8),
+            );
+            append_tlv_bytes(
+                buffer,
+                &mut payload_idx,
+                DEVICE_INFO_TAG_BOOT_NONCE,
+                &info.boot_nonce,
+            );
+        }
+        TxMessage::Diagnostics(diagnostics) => {
+            msg_type = MessageType::Diagnostics;
+            for counter in DiagCounter::ALL {
+                append_tlv_bytes(
+                    buffer,
+                    &mut payload_idx,
+                    counter as u8,
+                    &diagnostics.get(counter).to_be_bytes(),
+                );
+            }
+        }
+        // 文本回复由 TX 任务直接输出，不编码为帧
+        TxMessage::Console(_) => return 0,
+    }
+
+    buffer[header_len] = msg_type as u8;
+
+    // Calculate LEN = TYPE(1) + PAYLOAD
+    let payload_len = payload_idx - payload_start;
+    let total_body_len = 1 + payload_len;
+    buffer[header_len - 1] = total_body_len as u8;
+
+    // CRC
+    let crc_idx = payload_idx;
+    match version {
+        FrameVersion::V1 => {
+            buffer[crc_idx] = calculate_crc(&buffer[0..crc_idx]);
+        }
+        FrameVersion::V2 => {
+            let crc = crc16_ccitt(&buffer[0..crc_idx]).to_be_bytes();
+            buffer[crc_idx] = crc[0];
+            buffer[crc_idx + 1] = crc[1];
+        }
+    }
+
+    crc_idx + version.crc_len() // Total length
+}
+
+/// 给一个已编码的明文 v2 帧就地加上节点地址，返回新的帧长度
+///
+/// 必须在 `auth::sign` / `crypt::seal_frame` 之前调用，使地址受 MAC / AEAD 保护。
+/// `buffer[..len]` 尾部至少还有 1 字节空间。
+pub fn set_address(buffer: &mut [u8], len: usize, addr: u8) -> usize {
+    // SOF VER | SEQ LEN TYPE PAYLOAD | CRC -> SOF VER ADDR | SEQ ... | CRC
+    let crc_idx = len - 2;
+    buffer.copy_within(2..crc_idx, 3);
+    buffer[1] |= ADDR_FLAG;
+    buffer[2] = addr;
+
+    let crc_idx = crc_idx + 1;
+    let crc = crc16_ccitt(&buffer[..crc_idx]);
+    buffer[crc_idx..crc_idx + 2].copy_from_slice(&crc.to_be_bytes());
+    crc_idx + 2
+}
+
+fn append_sensor_tlv(buffer: &mut [u8], idx: &mut usize, data: &SensorData) {
+    match data {
+        SensorData::SoilMoisture(val) => {
+            append_tlv_u16(buffer, idx, SensorTag::SoilMoisture as u8, *val)
+        }
+        SensorData::Temperature(val) => {
+            append_tlv_i16(buffer, idx, SensorTag::Temperature as u8, *val)
+        }
+        SensorData::Humidity(val) => append_tlv_u16(buffer, idx, SensorTag::Humidity as u8, *val),
+        SensorData::LightIntensity(val) => {
+            append_tlv_u16(buffer, idx, SensorTag::LightIntensity as u8, *val)
+        }
+    }
+}
+
+fn append_actuator_tlv(buffer: &mut [u8], idx: &mut usize, status: &ActuatorFeedback) {
+    // Tag
+    buffer[*idx] = status.actuator as u8;
+    *idx += 1;
+    // Len (正常状态保持 v1 的 1 字节格式，失效保护时附加 FLAGS)
+    buffer[*idx] = if status.failsafe { 2 } else { 1 };
+    *idx += 1;
+    // Value
+    buffer[*idx] = if status.state { 1 } else { 0 };
+    *idx += 1;
+    if status.failsafe {
+        buffer[*idx] = ACTUATOR_FLAG_FAILSAFE;
+        *idx += 1;
+    }
+}
+
+/// 没有时间戳 (如尚未改变过状态的执行器) 时不附加
+fn append_timestamp_tlv(buffer: &mut [u8], idx: &mut usize, timestamp_ms: Option<u32>) {
+    if let Some(ts) = timestamp_ms {
+        append_tlv_bytes(buffer, idx, TAG_TIMESTAMP, &ts.to_be_bytes());
+    }
+}
+
+fn append_tlv_bytes(buffer: &mut [u8], idx: &mut usize, tag: u8, value: &[u8]) {
+    buffer[*idx] = tag;
+    buffer[*idx + 1] = value.len() as u8;
+    buffer[*idx + 2..*idx + 2 + value.len()].copy_from_slice(value);
+    *idx += 2 + value.len();
+}
+
+/// TAG 列表 TLV，值为每个 TAG 一字节
+fn append_tlv_tags(buffer: &mut [u8], idx: &mut usize, tag: u8, tags: impl Iterator<Item = u8>) {
+    let len_idx = *idx + 1;
+    buffer[*idx] = tag;
+    *idx += 2;
+    for t in tags {
+        buffer[*idx] = t;
+        *idx += 1;
+    }
+    buffer[len_idx] = (*idx - len_idx - 1) as u8;
+}
+
+fn append_tlv_u16(buffer: &mut [u8], idx: &mut usize, tag: u8, val: u16) {
+    buffer[*idx] = tag;
+    *idx += 1;
+    buffer[*idx] = 2; // Len
+    *idx += 1;
+    let bytes = val.to_be_bytes();
+    buffer[*idx] = bytes[0];
+    *idx += 1;
+    buffer[*idx] = bytes[1];
+    *idx += 1;
+}
+
+fn append_tlv_i16(buffer: &mut [u8], idx: &mut usize, tag: u8, val: i16) {
+    buffer[*idx] = tag;
+    *idx += 1;
+    buffer[*idx] = 2; // Len
+    *idx += 1;
+    let bytes = val.to_be_bytes();
+    buffer[*idx] = bytes[0];
+    *idx += 1;
+    buffer[*idx] = bytes[1];
+    *idx += 1;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{ActuatorStates, DeviceInfo, SensorReading};
+
+    fn encode(msg: TxMessage, version: FrameVersion, seq: u8) -> ([u8; 64], usize) {
+        let mut buf = [0u8; 64];
+        let len = encode_msg(&msg, version, seq, false, &mut buf);
+        (buf, len)
+    }
+
+    fn assert_valid(data: &[u8]) {
+        match check_frame(data) {
+            FrameError::Valid(len) => assert_eq!(len, data.len()),
+            _ => panic!("frame rejected: {:02X?}", data),
+        }
+    }
+
+    #[test]
+    fn crc16_check_value() {
+        assert_eq!(crc16_ccitt(b"123456789"), 0x29B1);
+    }
+
+    #[test]
+    fn encode_v1_temperature() {
+        // 协议文档 4.1 示例：温度 25.00°C
+        let (buf, len) = encode(
+            TxMessage::Sensor(SensorReading {
+                data: SensorData::Temperature(2500),
+                timestamp_ms: 0,
+            }),
+            FrameVersion::V1,
+            0x01,
+        );
+        assert_eq!(
+            &buf[..len],
+            &[0xAA, 0x05, 0x01, 0x02, 0x02, 0x09, 0xC4, 0x63]
+        );
+    }
+
+    #[test]
+    fn encode_v2_temperature() {
+        let (buf, len) = encode(
+            TxMessage::Sensor(SensorReading {
+                data: SensorData::Temperature(2500),
+                timestamp_ms: 0,
+            }),
+            FrameVersion::V2,
+            0x01,
+        );
+        assert_eq!(
+            &buf[..len],
+            &[
+                0xAA, 0x82, 0x01, 0x05, 0x01, 0x02, 0x02, 0x09, 0xC4, 0x6C, 0xEA
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_v2_heartbeat() {
+        let (buf, len) = encode(
+            TxMessage::Heartbeat { uptime_s: 60 },
+            FrameVersion::V2,
+            0x00,
+        );
+        assert_eq!(
+            &buf[..len],
+            &[
+                0xAA, 0x82, 0x00, 0x07, 0x20, 0x01, 0x04, 0x00, 0x00, 0x00, 0x3C, 0x5D, 0xCB
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_snapshot_packs_all_readings() {
+        let snapshot = SensorSnapshot {
+            soil_moisture: Some(0x0800),
+            temperature: Some(-150),
+            humidity: Some(5000),
+            light_intensity: Some(321),
+            ..SensorSnapshot::new()
+        };
+        let (buf, len) = encode(TxMessage::Snapshot(snapshot), FrameVersion::V1, 0);
+        assert_valid(&buf[..len]);
+        let parts = frame_parts(&buf[..len]);
+        assert_eq!(parts.msg_type, MessageType::SensorReport as u8);
+        assert_eq!(
+            parts.payload,
+            &[
+                0x01, 0x02, 0x08, 0x00, // SoilMoisture
+                0x02, 0x02, 0xFF, 0x6A, // Temperature -1.50
+                0x03, 0x02, 0x13, 0x88, // Humidity 50.00
+                0x04, 0x02, 0x01, 0x41, // LightIntensity
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_partial_snapshot() {
+        let mut snapshot = SensorSnapshot::default();
+        snapshot.update(SensorData::LightIntensity(100));
+        let (buf, len) = encode(TxMessage::Snapshot(snapshot), FrameVersion::V2, 3);
+        assert_valid(&buf[..len]);
+        assert_eq!(frame_parts(&buf[..len]).payload, &[0x04, 0x02, 0x00, 0x64]);
+    }
+
+    #[test]
+    fn encoded_frames_pass_check() {
+        let ack = TxMessage::Ack(CommandAck {
+            tag: ActuatorTag::Pump as u8,
+            success: true,
+            reason: NackReason::None,
+            seq: 0x07,
+        });
+        for version in [FrameVersion::V1, FrameVersion::V2] {
+            let (buf, len) = encode(ack, version, 0x55);
+            assert_valid(&buf[..len]);
+            let parts = frame_parts(&buf[..len]);
+            assert_eq!(parts.version, version);
+            assert_eq!(parts.msg_type, MessageType::CommandAck as u8);
+            assert_eq!(parts.payload, &[0x11, 0x01, 0x01]);
+        }
+    }
+
+    #[test]
+    fn ack_echoes_command_seq() {
+        let ack = TxMessage::Ack(CommandAck {
+            tag: ActuatorTag::Pump as u8,
+            success: true,
+            reason: NackReason::None,
+            seq: 0x07,
+        });
+        let (buf, len) = encode(ack, FrameVersion::V2, 0x55);
+        assert_eq!(
+            &buf[..len],
+            &[0xAA, 0x82, 0x07, 0x04, 0x11, 0x11, 0x01, 0x01, 0x96, 0xA6]
+        );
+    }
+
+    #[test]
+    fn v1_frames_have_zero_seq() {
+        let frame = [0xAA, 0x04, 0x10, 0x10, 0x01, 0x01, 0xAE];
+        assert_eq!(frame_parts(&frame).seq, 0);
+    }
+
+    #[test]
+    fn check_v1_command_vectors() {
+        // 协议文档 4.2 示例
+        assert_valid(&[0xAA, 0x04, 0x10, 0x10, 0x01, 0x01, 0xAE]);
+        assert_valid(&[0xAA, 0x05, 0x10, 0x13, 0x02, 0x00, 0x64, 0xCA]);
+    }
+
+    #[test]
+    fn check_v2_command_vector() {
+        let frame = [0xAA, 0x82, 0x07, 0x04, 0x10, 0x10, 0x01, 0x01, 0xD7, 0x22];
+        assert_valid(&frame);
+        let parts = frame_parts(&frame);
+        assert_eq!(parts.version, FrameVersion::V2);
+        assert_eq!(parts.seq, 0x07);
+        assert_eq!(parts.msg_type, MessageType::Command as u8);
+        assert_eq!(parts.payload, &[0x10, 0x01, 0x01]);
+    }
+
+    #[test]
+    fn long_v1_frame_rejected_with_nack() {
+        // LEN = 0x80：TYPE + 127 字节 Payload，第一个 TLV 是风扇命令
+        let mut frame = [0u8; 131];
+        frame[..6].copy_from_slice(&[0xAA, 0x80, 0x10, 0x10, 0x01, 0x01]);
+        frame[130] = frame[..130].iter().fold(0, |crc, b| crc ^ b);
+        assert!(matches!(check_frame(&frame), FrameError::Valid(131)));
+
+        let Ok(RxMessage::Command { seq: 0, commands }) = decode_frame(&frame) else {
+            panic!("expected a command");
+        };
+        assert_eq!(
+            commands.as_slice(),
+            &[Err(CommandAck::rejected(0x10, NackReason::BadLength, 0))]
+        );
+
+        // 其它消息直接丢弃
+        frame[2] = MessageType::Heartbeat as u8;
+        frame[130] = frame[..130].iter().fold(0, |crc, b| crc ^ b);
+        assert_eq!(decode_frame(&frame), Err(DecodeError::Malformed));
+    }
+
+    #[test]
+    fn addressed_frame() {
+        let mut buf = [0u8; 16];
+        let plain = [0xAA, 0x82, 0x07, 0x04, 0x10, 0x10, 0x01, 0x01, 0xD7, 0x22];
+        buf[..plain.len()].copy_from_slice(&plain);
+        let len = set_address(&mut buf, plain.len(), 0x05);
+        assert_eq!(len, plain.len() + 1);
+        assert_eq!(&buf[..4], &[0xAA, 0x92, 0x05, 0x07]);
+        assert_valid(&buf[..len]);
+
+        let parts = frame_parts(&buf[..len]);
+        assert_eq!(parts.addr, Some(0x05));
+        assert_eq!(parts.seq, 0x07);
+        assert_eq!(parts.msg_type, MessageType::Command as u8);
+        assert_eq!(parts.payload, &[0x10, 0x01, 0x01]);
+        assert!(decode_frame(&buf[..len]).is_ok());
+
+        // 不带地址的帧
+        assert_eq!(frame_parts(&plain).addr, None);
+    }
+
+    #[test]
+    fn v2_detects_swapped_bytes() {
+        // XOR 无法发现字节交换，CRC-16 可以
+        let v1_swapped = [0xAA, 0x05, 0x10, 0x13, 0x02, 0x64, 0x00, 0xCA];
+        assert!(matches!(check_frame(&v1_swapped), FrameError::Valid(_)));
+
+        let v2_swapped = [0xAA, 0x82, 0x07, 0x04, 0x10, 0x01, 0x10, 0x01, 0xD7, 0x22];
+        assert!(matches!(check_frame(&v2_swapped), FrameError::CrcError));
+    }
+
+    #[test]
+    fn check_rejects_bad_input() {
+        assert!(matches!(check_frame(&[0xAA, 0x04]), FrameError::Incomplete));
+        assert!(matches!(
+            check_frame(&[0xAA, 0x82, 0x07, 0x04, 0x10, 0x10, 0x01]),
+            FrameError::Incomplete
+        ));
+        assert!(matches!(
+            check_frame(&[0x55, 0x04, 0x10, 0x10, 0x01, 0x01, 0xAE]),
+            FrameError::HeaderError
+        ));
+        // 不是合法版本字节的值按超长 v1 帧的 LEN 处理，认证与加密标志也不能同时出现
+        assert!(matches!(
+            check_frame(&[0xAA, 0x8F, 0x00, 0x01, 0x20, 0x00, 0x00]),
+            FrameError::Incomplete
+        ));
+        assert!(matches!(
+            check_frame(&[0xAA, 0xE2, 0x00, 0x01, 0x20, 0x00, 0x00]),
+            FrameError::Incomplete
+        ));
+        // LEN 为 0 (没有 TYPE)
+        assert!(matches!(
+            check_frame(&[0xAA, 0x00, 0x10, 0x10]),
+            FrameError::HeaderError
+        ));
+        assert!(matches!(
+            check_frame(&[0xAA, 0x04, 0x10, 0x10, 0x01, 0x01, 0x00]),
+            FrameError::CrcError
+        ));
+    }
+
+    /// 按给定字段构造一帧 (测试用)
+    fn build(version: FrameVersion, seq: u8, msg_type: u8, payload: &[u8]) -> ([u8; 64], usize) {
+        let mut buf = [0u8; 64];
+        let mut idx = 0;
+        buf[idx] = SOF;
+        idx += 1;
+        if version == FrameVersion::V2 {
+            buf[idx] = VERSION_FLAG | version as u8;
+            buf[idx + 1] = seq;
+            idx += 2;
+        }
+        buf[idx] = 1 + payload.len() as u8;
+        buf[idx + 1] = msg_type;
+        idx += 2;
+        buf[idx..idx + payload.len()].copy_from_slice(payload);
+        idx += payload.len();
+        match version {
+            FrameVersion::V1 => {
+                buf[idx] = calculate_crc(&buf[..idx]);
+                idx += 1;
+            }
+            FrameVersion::V2 => {
+                let crc = crc16_ccitt(&buf[..idx]).to_be_bytes();
+                buf[idx..idx + 2].copy_from_slice(&crc);
+                idx += 2;
+            }
+        }
+        (buf, idx)
+    }
+
+    #[test]
+    fn decode_sensor_report() {
+        let snapshot = SensorSnapshot {
+            soil_moisture: Some(1234),
+            temperature: Some(-250),
+            humidity: None,
+            light_intensity: Some(800),
+            ..SensorSnapshot::new()
+        };
+        let (buf, len) = encode(TxMessage::Snapshot(snapshot), FrameVersion::V2, 1);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::SensorReport(snapshot))
+        );
+
+        // 未知 TAG 被跳过
+        let (buf, len) = build(
+            FrameVersion::V1,
+            0,
+            0x01,
+            &[0x7F, 0x01, 0x00, 0x03, 0x02, 0x13, 0x88],
+        );
+        let mut expected = SensorSnapshot::default();
+        expected.update(SensorData::Humidity(5000));
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::SensorReport(expected))
+        );
+    }
+
+    #[test]
+    fn decode_actuator_status() {
+        let feedback = ActuatorFeedback {
+            actuator: ActuatorTag::Light,
+            state: false,
+            failsafe: false,
+            timestamp_ms: None,
+        };
+        let (buf, len) = encode(TxMessage::Actuator(feedback), FrameVersion::V1, 0);
+        let Ok(RxMessage::ActuatorStatus(feedbacks)) = decode_frame(&buf[..len]) else {
+            panic!("not an actuator status");
+        };
+        assert_eq!(feedbacks.as_slice(), &[feedback]);
+    }
+
+    #[test]
+    fn actuator_status_failsafe_flag() {
+        let feedback = ActuatorFeedback {
+            actuator: ActuatorTag::Pump,
+            state: false,
+            failsafe: true,
+            timestamp_ms: None,
+        };
+        let (buf, len) = encode(TxMessage::Actuator(feedback), FrameVersion::V2, 4);
+        assert_eq!(frame_parts(&buf[..len]).payload, &[0x11, 0x02, 0x00, 0x01]);
+        let Ok(RxMessage::ActuatorStatus(feedbacks)) = decode_frame(&buf[..len]) else {
+            panic!("not an actuator status");
+        };
+        assert_eq!(feedbacks.as_slice(), &[feedback]);
+    }
+
+    #[test]
+    fn timestamps_follow_their_readings() {
+        let mut snapshot = SensorSnapshot::new();
+        snapshot.record(SensorReading {
+            data: SensorData::Humidity(5000),
+            timestamp_ms: 0x0001_E240,
+        });
+        // 没有时间戳的读数 (例如来自旧的存储) 不附加时间戳 TLV
+        snapshot.update(SensorData::LightIntensity(300));
+
+        let mut buf = [0u8; 64];
+        let len = encode_msg(
+            &TxMessage::Snapshot(snapshot),
+            FrameVersion::V2,
+            3,
+            true,
+            &mut buf,
+        );
+        assert_eq!(
+            frame_parts(&buf[..len]).payload,
+            &[
+                0x03, 0x02, 0x13, 0x88, 0xF0, 0x04, 0x00, 0x01, 0xE2, 0x40, 0x04, 0x02, 0x01, 0x2C
+            ]
+        );
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::SensorReport(snapshot))
+        );
+
+        // 关闭时帧格式与以前相同
+        let len = encode_msg(
+            &TxMessage::Snapshot(snapshot),
+            FrameVersion::V2,
+            3,
+            false,
+            &mut buf,
+        );
+        assert_eq!(
+            frame_parts(&buf[..len]).payload,
+            &[0x03, 0x02, 0x13, 0x88, 0x04, 0x02, 0x01, 0x2C]
+        );
+    }
+
+    #[test]
+    fn actuator_timestamp_round_trip() {
+        let feedback = ActuatorFeedback {
+            actuator: ActuatorTag::Fan,
+            state: true,
+            failsafe: false,
+            timestamp_ms: Some(1500),
+        };
+        let mut buf = [0u8; 64];
+        let len = encode_msg(
+            &TxMessage::Actuator(feedback),
+            FrameVersion::V1,
+            0,
+            true,
+            &mut buf,
+        );
+        assert_eq!(
+            frame_parts(&buf[..len]).payload,
+            &[0x10, 0x01, 0x01, 0xF0, 0x04, 0x00, 0x00, 0x05, 0xDC]
+        );
+        let Ok(RxMessage::ActuatorStatus(feedbacks)) = decode_frame(&buf[..len]) else {
+            panic!("not an actuator status");
+        };
+        assert_eq!(feedbacks.as_slice(), &[feedback]);
+    }
+
+    #[test]
+    fn orphan_timestamp_is_malformed() {
+        let ts = [0xF0, 0x04, 0x00, 0x00, 0x00, 0x01];
+        let (buf, len) = build(FrameVersion::V1, 0, 0x01, &ts);
+        assert_eq!(decode_frame(&buf[..len]), Err(DecodeError::Malformed));
+        let (buf, len) = build(FrameVersion::V1, 0, 0x02, &ts);
+        assert_eq!(decode_frame(&buf[..len]), Err(DecodeError::Malformed));
+        // 长度错误
+        let (buf, len) = build(
+            FrameVersion::V1,
+            0,
+            0x01,
+            &[0x03, 0x02, 0x13, 0x88, 0xF0, 0x02, 0x00, 0x01],
+        );
+        assert_eq!(decode_frame(&buf[..len]), Err(DecodeError::Malformed));
+    }
+
+    #[test]
+    fn security_event_round_trip() {
+        let event = TxMessage::SecurityEvent {
+            reason: AuthError::Replay,
+            counter: Some(41),
+        };
+        let (buf, len) = encode(event, FrameVersion::V2, 9);
+        assert_eq!(
+            frame_parts(&buf[..len]).payload,
+            &[0x01, 0x01, 0x02, 0x02, 0x04, 0x00, 0x00, 0x00, 0x29]
+        );
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::SecurityEvent {
+                reason: AuthError::Replay,
+                counter: Some(41),
+            })
+        );
+
+        let (buf, len) = build(FrameVersion::V1, 0, 0x21, &[0x01, 0x01, 0x03]);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::SecurityEvent {
+                reason: AuthError::Missing,
+                counter: None,
+            })
+        );
+    }
+
+    #[test]
+    fn decode_configure() {
+        let (buf, len) = build(
+            FrameVersion::V2,
+            0x21,
+            0x40,
+            &[
+                0x01, 0x01, 0x01, // 时间戳开
+                0x01, 0x02, 0x00, 0x00, // 长度错误
+                0x7E, 0x01, 0x00, // 未知选项
+                0x02, 0x04, 0x00, 0x00, 0x01, 0x2C, // 统计窗口 300 s
+            ],
+        );
+        let Ok(RxMessage::Configure { seq, options }) = decode_frame(&buf[..len]) else {
+            panic!("not a configure message");
+        };
+        assert_eq!(seq, 0x21);
+        assert_eq!(
+            options.as_slice(),
+            &[
+                Ok(ConfigOption::Timestamps(true)),
+                Err(CommandAck::rejected(0x01, NackReason::BadLength, 0x21)),
+                Err(CommandAck::rejected(0x7E, NackReason::UnknownTag, 0x21)),
+                Ok(ConfigOption::StatsWindow(300)),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_set_interval() {
+        let (buf, len) = build(
+            FrameVersion::V2,
+            0x22,
+            0x41,
+            &[
+                0x02, 0x04, 0x00, 0x00, 0x13, 0x88, // 温度 5000 ms
+                0x04, 0x02, 0x01, 0x00, // 长度错误
+                0x09, 0x04, 0x00, 0x00, 0x03, 0xE8, // 未知传感器
+            ],
+        );
+        let Ok(RxMessage::SetInterval { seq, intervals }) = decode_frame(&buf[..len]) else {
+            panic!("not a set interval message");
+        };
+        assert_eq!(seq, 0x22);
+        assert_eq!(
+            intervals.as_slice(),
+            &[
+                Ok(SensorInterval {
+                    sensor: SensorTag::Temperature,
+                    interval_ms: 5000,
+                }),
+                Err(CommandAck::rejected(0x04, NackReason::BadLength, 0x22)),
+                Err(CommandAck::rejected(0x09, NackReason::UnknownTag, 0x22)),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_set_deadband() {
+        let (buf, len) = build(
+            FrameVersion::V2,
+            0x23,
+            0x42,
+            &[
+                0x01, 0x06, 0x00, 0x14, 0x00, 0x00, 0xEA, 0x60, // 土壤 ±20，最长 60s
+                0x04, 0x00, // 取消光照死区
+                0x02, 0x02, 0x00, 0x0A, // 长度错误
+            ],
+        );
+        let Ok(RxMessage::SetDeadband { seq, deadbands }) = decode_frame(&buf[..len]) else {
+            panic!("not a set deadband message");
+        };
+        assert_eq!(seq, 0x23);
+        assert_eq!(
+            deadbands.as_slice(),
+            &[
+                Ok(SensorDeadband {
+                    sensor: SensorTag::SoilMoisture,
+                    band: Some(Deadband {
+                        threshold: 20,
+                        max_silence_ms: 60_000,
+                    }),
+                }),
+                Ok(SensorDeadband {
+                    sensor: SensorTag::LightIntensity,
+                    band: None,
+                }),
+                Err(CommandAck::rejected(0x02, NackReason::BadLength, 0x23)),
+            ]
+        );
+    }
+
+    #[test]
+    fn encode_actuator_states() {
+        let mut states = ActuatorStates::new();
+        states.update(ActuatorFeedback {
+            actuator: ActuatorTag::Pump,
+            state: true,
+            failsafe: false,
+            timestamp_ms: None,
+        });
+        states.update(ActuatorFeedback {
+            actuator: ActuatorTag::Buzzer,
+            state: true,
+            failsafe: false,
+            timestamp_ms: None,
+        });
+        let (buf, len) = encode(TxMessage::ActuatorStates(states), FrameVersion::V2, 2);
+        assert_eq!(
+            frame_parts(&buf[..len]).payload,
+            &[
+                0x10, 0x01, 0x00, 0x11, 0x01, 0x01, 0x12, 0x01, 0x00, 0x13, 0x01, 0x01
+            ]
+        );
+
+        let Ok(RxMessage::ActuatorStatus(feedbacks)) = decode_frame(&buf[..len]) else {
+            panic!("not an actuator status");
+        };
+        assert!(
+            feedbacks
+                .iter()
+                .eq(states.feedbacks().collect::<heapless::Vec<_, 4>>().iter())
+        );
+    }
+
+    #[test]
+    fn decode_queries() {
+        let (buf, len) = build(FrameVersion::V2, 0x05, 0x30, &[]);
+        assert_eq!(decode_frame(&buf[..len]), Ok(RxMessage::GetSensors));
+        let (buf, len) = build(FrameVersion::V1, 0, 0x31, &[]);
+        assert_eq!(decode_frame(&buf[..len]), Ok(RxMessage::GetActuators));
+        let (buf, len) = build(FrameVersion::V1, 0, 0x32, &[]);
+        assert_eq!(decode_frame(&buf[..len]), Ok(RxMessage::GetDeviceInfo));
+        let (buf, len) = build(FrameVersion::V2, 0x06, 0x33, &[]);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::GetDiagnostics { reset: false })
+        );
+        let (buf, len) = build(FrameVersion::V2, 0x07, 0x33, &[0x01, 0x01, 0x01]);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::GetDiagnostics { reset: true })
+        );
+        let (buf, len) = build(FrameVersion::V2, 0x08, 0x33, &[0x01, 0x02, 0x00, 0x01]);
+        assert_eq!(decode_frame(&buf[..len]), Err(DecodeError::Malformed));
+    }
+
+    #[test]
+    fn diagnostics_round_trip() {
+        let mut diagnostics = Diagnostics::default();
+        diagnostics.counters[DiagCounter::RxCrcErrors.index()] = 3;
+        diagnostics.counters[DiagCounter::Commands.index()] = 0x0102_0304;
+        // 全部计数器的帧超过 64 字节，用与 TX 任务相同大小的缓冲区
+        let mut buf = [0u8; 96];
+        let len = encode_msg(
+            &TxMessage::Diagnostics(diagnostics),
+            FrameVersion::V2,
+            1,
+            false,
+            &mut buf,
+        );
+        assert_valid(&buf[..len]);
+        let payload = frame_parts(&buf[..len]).payload;
+        assert_eq!(payload.len(), DiagCounter::ALL.len() * 6);
+        assert_eq!(&payload[..6], &[0x01, 0x04, 0x00, 0x00, 0x00, 0x03]);
+        assert_eq!(&payload[54..60], &[0x0A, 0x04, 0x01, 0x02, 0x03, 0x04]);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::Diagnostics(diagnostics))
+        );
+    }
+
+    #[test]
+    fn sensor_stats_round_trip() {
+        let mut stats = [None; SensorTag::ALL.len()];
+        stats[SensorTag::Temperature.index()] = Some(SensorStats {
+            sensor: SensorTag::Temperature,
+            min: -520,
+            max: 310,
+            mean: -12,
+            count: 60,
+        });
+        stats[SensorTag::LightIntensity.index()] = Some(SensorStats {
+            sensor: SensorTag::LightIntensity,
+            min: 0,
+            max: 54612,
+            mean: 40000,
+            count: 0x0001_0002,
+        });
+        let (buf, len) = encode(TxMessage::Stats(stats), FrameVersion::V2, 4);
+        assert_valid(&buf[..len]);
+        let parts = frame_parts(&buf[..len]);
+        assert_eq!(parts.msg_type, MessageType::SensorStats as u8);
+        // 窗口内没有样本的传感器不编码
+        assert_eq!(
+            parts.payload,
+            &[
+                0x02, 0x0A, 0xFD, 0xF8, 0x01, 0x36, 0xFF, 0xF4, 0x00, 0x00, 0x00, 0x3C, //
+                0x04, 0x0A, 0x00, 0x00, 0xD5, 0x54, 0x9C, 0x40, 0x00, 0x01, 0x00, 0x02,
+            ]
+        );
+        assert_eq!(decode_frame(&buf[..len]), Ok(RxMessage::SensorStats(stats)));
+    }
+
+    #[test]
+    fn device_info_round_trip() {
+        let info = DeviceInfo {
+            firmware: [0, 1, 0],
+            protocol: FrameVersion::LATEST as u8,
+            uid: [
+                0x10, 0x11, 0x12, 0x13, 0x14, 0x15, 0x16, 0x17, 0x18, 0x19, 0x1A, 0x1B,
+            ],
+            reset_cause: ResetCause::IndependentWatchdog,
+            sensors: &[SensorTag::Temperature, SensorTag::Humidity],
+            actuators: &ActuatorTag::ALL,
+            boot_nonce: [0xB0, 0x07, 0, 0, 0, 0, 0, 0x01],
+        };
+        // 最长的明文上行帧 (DeviceInfo 不加密)
+        let (buf, len) = encode(TxMessage::DeviceInfo(info), FrameVersion::V2, 0);
+        assert_valid(&buf[..len]);
+        assert_eq!(
+            frame_parts(&buf[..len]).payload[..7],
+            [0x01, 0x03, 0x00, 0x01, 0x00, 0x02, 0x01]
+        );
+
+        let Ok(RxMessage::DeviceInfo {
+            firmware,
+            protocol,
+            uid,
+            reset_cause,
+            sensors,
+            actuators,
+            boot_nonce,
+        }) = decode_frame(&buf[..len])
+        else {
+            panic!("not a device info");
+        };
+        assert_eq!(firmware, info.firmware);
+        assert_eq!(protocol, 2);
+        assert_eq!(uid, info.uid);
+        assert_eq!(reset_cause, ResetCause::IndependentWatchdog);
+        assert_eq!(sensors.as_slice(), info.sensors);
+        assert_eq!(actuators.as_slice(), info.actuators);
+        assert_eq!(boot_nonce, info.boot_nonce);
+    }
+
+    #[test]
+    fn device_info_rejects_bad_uid_length() {
+        let (buf, len) = build(FrameVersion::V1, 0, 0x03, &[0x03, 0x02, 0xAB, 0xCD]);
+        assert_eq!(decode_frame(&buf[..len]), Err(DecodeError::Malformed));
+    }
+
+    #[test]
+    fn decode_command() {
+        // 协议文档 4.2 示例1：打开风扇
+        let frame = [0xAA, 0x04, 0x10, 0x10, 0x01, 0x01, 0xAE];
+        let Ok(RxMessage::Command { seq, commands }) = decode_frame(&frame) else {
+            panic!("not a command");
+        };
+        assert_eq!(seq, 0);
+        assert_eq!(
+            commands.as_slice(),
+            &[Ok(ControlCommand {
+                actuator: ActuatorTag::Fan,
+                state: true,
+                duration_ms: 0,
+                seq: 0,
+                ack: AckMode::Frame,
+            })]
+        );
+
+        // v2：水泵关闭 + 蜂鸣器响 100ms
+        let (buf, len) = build(
+            FrameVersion::V2,
+            0x30,
+            0x10,
+            &[0x11, 0x01, 0x00, 0x13, 0x02, 0x00, 0x64],
+        );
+        let Ok(RxMessage::Command { seq, commands }) = decode_frame(&buf[..len]) else {
+            panic!("not a command");
+        };
+        assert_eq!(seq, 0x30);
+        assert_eq!(
+            commands.as_slice(),
+            &[
+                Ok(ControlCommand {
+                    actuator: ActuatorTag::Pump,
+                    state: false,
+                    duration_ms: 0,
+                    seq: 0x30,
+                    ack: AckMode::Frame,
+                }),
+                Ok(ControlCommand {
+                    actuator: ActuatorTag::Buzzer,
+                    state: true,
+                    duration_ms: 100,
+                    seq: 0x30,
+                    ack: AckMode::Frame,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn decode_command_rejects_bad_entries() {
+        // 未知 TAG 0x1F 不能被当作风扇执行；长度 3 的 TLV 被拒绝
+        let (buf, len) = build(
+            FrameVersion::V2,
+            0x31,
+            0x10,
+            &[
+                0x1F, 0x01, 0x01, 0x10, 0x03, 0x00, 0x00, 0x01, 0x12, 0x01, 0x01,
+            ],
+        );
+        let Ok(RxMessage::Command { commands, .. }) = decode_frame(&buf[..len]) else {
+            panic!("not a command");
+        };
+        assert_eq!(
+            commands.as_slice(),
+            &[
+                Err(CommandAck::rejected(0x1F, NackReason::UnknownTag, 0x31)),
+                Err(CommandAck::rejected(0x10, NackReason::BadLength, 0x31)),
+                Ok(ControlCommand {
+                    actuator: ActuatorTag::Light,
+                    state: true,
+                    duration_ms: 0,
+                    seq: 0x31,
+                    ack: AckMode::Frame,
+                }),
+            ]
+        );
+    }
+
+    #[test]
+    fn nack_round_trip() {
+        let nack = CommandAck::rejected(0x11, NackReason::Busy, 0x08);
+        let (buf, len) = encode(TxMessage::Ack(nack), FrameVersion::V2, 0);
+        let parts = frame_parts(&buf[..len]);
+        assert_eq!(parts.payload, &[0x11, 0x02, 0x00, 0x03]);
+        assert_eq!(decode_frame(&buf[..len]), Ok(RxMessage::CommandAck(nack)));
+    }
+
+    #[test]
+    fn v1_nack_keeps_original_layout() {
+        let nack = CommandAck::rejected(0x11, NackReason::Interlocked, 0);
+        let (buf, len) = encode(TxMessage::Ack(nack), FrameVersion::V1, 0);
+        assert_eq!(&buf[..len - 1], &[SOF, 0x04, 0x11, 0x11, 0x01, 0x00]);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::CommandAck(CommandAck::rejected(
+                0x11,
+                NackReason::None,
+                0
+            )))
+        );
+    }
+
+    #[test]
+    fn tag_conversions_are_strict() {
+        assert_eq!(ActuatorTag::try_from(0x13), Ok(ActuatorTag::Buzzer));
+        assert_eq!(ActuatorTag::try_from(0x14), Err(0x14));
+        assert_eq!(ActuatorTag::try_from(0x00), Err(0x00));
+        assert_eq!(SensorTag::try_from(0x04), Ok(SensorTag::LightIntensity));
+        assert_eq!(SensorTag::try_from(0x10), Err(0x10));
+    }
+
+    #[test]
+    fn decode_command_ack() {
+        let ack = CommandAck {
+            tag: ActuatorTag::Buzzer as u8,
+            success: true,
+            reason: NackReason::None,
+            seq: 0x42,
+        };
+        let (buf, len) = encode(TxMessage::Ack(ack), FrameVersion::V2, 0);
+        assert_eq!(decode_frame(&buf[..len]), Ok(RxMessage::CommandAck(ack)));
+    }
+
+    #[test]
+    fn decode_host_ack() {
+        let (buf, len) = build(FrameVersion::V2, 0x21, 0x12, &[]);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::HostAck { seq: 0x21 })
+        );
+    }
+
+    #[test]
+    fn decode_heartbeat() {
+        let (buf, len) = encode(
+            TxMessage::Heartbeat { uptime_s: 86_400 },
+            FrameVersion::V1,
+            0,
+        );
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::Heartbeat {
+                uptime_s: Some(86_400)
+            })
+        );
+
+        // 上位机心跳可以是空 Payload
+        let (buf, len) = build(FrameVersion::V2, 0x09, 0x20, &[]);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Ok(RxMessage::Heartbeat { uptime_s: None })
+        );
+    }
+
+    #[test]
+    fn decode_errors() {
+        let (buf, len) = build(FrameVersion::V2, 0, 0x7E, &[]);
+        assert_eq!(
+            decode_frame(&buf[..len]),
+            Err(DecodeError::UnknownType(0x7E))
+        );
+
+        // TLV 长度超出 Payload
+        let (buf, len) = build(FrameVersion::V1, 0, 0x10, &[0x10, 0x02, 0x01]);
+        assert_eq!(decode_frame(&buf[..len]), Err(DecodeError::Malformed));
+
+        // 状态反馈缺少 Value
+        let (buf, len) = build(FrameVersion::V1, 0, 0x02, &[0x10, 0x00]);
+        assert_eq!(decode_frame(&buf[..len]), Err(DecodeError::Malformed));
+
+        assert_eq!(
+            decode_frame(&[0xAA, 0x04, 0x10, 0x10, 0x01, 0x01, 0x00]),
+            Err(DecodeError::Crc)
+        );
+        assert_eq!(
+            decode_frame(&[0xAA, 0x04, 0x10]),
+            Err(DecodeError::Incomplete)
+        );
+        assert_eq!(
+            decode_frame(&[0x00, 0x04, 0x10, 0x10, 0x01, 0x01, 0xAE]),
+            Err(DecodeError::Header)
+        );
+    }
+}
@@ -0,0 +1,67 @@
+//! 设备信息 (DeviceInfo)
+//!
+//! 上电时主动上报一次，之后可由上位机通过 `GetDeviceInfo` 查询。
+
+use crate::config::{ACTUATORS_PRESENT, SENSORS_PRESENT};
+use crate::frame::FrameVersion;
+use crate::protocol::{DeviceInfo, ResetCause};
+use crate::session;
+use core::sync::atomic::{AtomicU8, Ordering};
+use embassy_stm32::pac::RCC;
+
+/// 启动时读取到的复位原因
+static RESET_CAUSE: AtomicU8 = AtomicU8::new(ResetCause::Unknown as u8);
+
+/// 固件版本，取自 Cargo.toml
+const FIRMWARE_VERSION: [u8; 3] = [
+    parse_u8(env!("CARGO_PKG_VERSION_MAJOR")),
+    parse_u8(env!("CARGO_PKG_VERSION_MINOR")),
+    parse_u8(env!("CARGO_PKG_VERSION_PATCH")),
+];
+
+const fn parse_u8(s: &str) -> u8 {
+    let bytes = s.as_bytes();
+    let mut value = 0u8;
+    let mut i = 0;
+    while i < bytes.len() {
+        value = value * 10 + (bytes[i] - b'0');
+        i += 1;
+    }
+    value
+}
+
+/// 读取 RCC_CSR 的复位标志并清除，只应在启动时调用一次
+pub fn capture_reset_cause() {
+    let csr = RCC.csr().read();
+    // 上电复位同时会置位 PINRSTF，因此先判断其它标志
+    let cause = if csr.lpwrrstf() {
+        ResetCause::LowPower
+    } else if csr.wwdgrstf() {
+        ResetCause::WindowWatchdog
+    } else if csr.iwdgrstf() {
+        ResetCause::IndependentWatchdog
+    } else if csr.sftrstf() {
+        ResetCause::Software
+    } else if csr.porrstf() {
+        ResetCause::PowerOn
+    } else if csr.pinrstf() {
+        ResetCause::Pin
+    } else {
+        ResetCause::Unknown
+    };
+    // 清除标志，否则下次复位后仍会读到本次的原因
+    RCC.csr().modify(|w| w.set_rmvf(true));
+    RESET_CAUSE.store(cause as u8, Ordering::Relaxed);
+}
+
+pub fn device_info() -> DeviceInfo {
+    DeviceInfo {
+        firmware: FIRMWARE_VERSION,
+        protocol: FrameVersion::LATEST as u8,
+        uid: *embassy_stm32::uid::uid(),
+        reset_cause: RESET_CAUSE.load(Ordering::Relaxed).into(),
+        sensors: SENSORS_PRESENT,
+        actuators: ACTUATORS_PRESENT,
+        boot_nonce: session::boot_nonce(),
+    }
+}
@@ -0,0 +1,95 @@
+//! 上位机链路状态
+//!
+//! RX 任务每收到一个合法帧就调用 `frame_received`；`heartbeat_task` 周期性发送带运行时间的心跳，
+//! 并在超过 `config::LINK_TIMEOUT_MS` 没有收到任何合法帧时把链路标记为丢失。
+//! 超时判断 (以及由此触发的失效保护) 要等上位机发过心跳才开始：v1 上位机从不发心跳，
+//! 只在需要时发命令，不能因为它安静了一会儿就判定链路丢失。
+//! 其它任务可以通过 `state()` 查询，或用 `LINK_STATE.receiver()` 等待状态变化。
+
+use crate::config::{HEARTBEAT_PERIOD_MS, LINK_TIMEOUT_MS, UART_TX_CHANNEL};
+use crate::protocol::TxMessage;
+use core::cell::Cell;
+use core::sync::atomic::{AtomicBool, Ordering};
+use embassy_executor::task;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::watch::Watch;
+use embassy_time::{Duration, Instant, Ticker};
+
+/// 链路状态
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum LinkState {
+    /// 上电后还没有收到过上位机的帧
+    Unknown,
+    /// 最近 `LINK_TIMEOUT_MS` 内收到过合法帧
+    Up,
+    /// 超时未收到合法帧
+    Lost,
+}
+
+/// 链路状态，状态变化时通知所有接收者
+pub static LINK_STATE: Watch<CriticalSectionRawMutex, LinkState, 6> =
+    Watch::new_with(LinkState::Unknown);
+
+/// 最近一次收到合法帧的时间
+static LAST_RX: Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>> = Mutex::new(Cell::new(None));
+
+/// 上位机会定期发帧，开始做超时判断
+static ARMED: AtomicBool = AtomicBool::new(false);
+
+/// 收到上位机的心跳 (或其它说明上位机会定期发帧的请求)，此后开始检测链路超时
+pub fn arm() {
+    ARMED.store(true, Ordering::Relaxed);
+}
+
+/// 收到上位机的合法帧
+pub fn frame_received() {
+    LAST_RX.lock(|last| last.set(Some(Instant::now())));
+    set_state(LinkState::Up);
+}
+
+/// 当前链路状态
+pub fn state() -> LinkState {
+    LINK_STATE.try_get().unwrap_or(LinkState::Unknown)
+}
+
+/// 距离最近一次收到合法帧的时间，从未收到时为 None
+pub fn since_last_rx() -> Option<Duration> {
+    LAST_RX.lock(|last| last.get()).map(|t| t.elapsed())
+}
+
+fn set_state(new: LinkState) {
+    LINK_STATE.sender().send_if_modified(|state| {
+        if *state == Some(new) {
+            false
+        } else {
+            *state = Some(new);
+            true
+        }
+    });
+}
+
+/// 周期发送心跳并检测链路超时
+#[task]
+pub async fn heartbeat_task() {
+    let timeout = Duration::from_millis(LINK_TIMEOUT_MS);
+    let mut ticker = Ticker::every(Duration::from_millis(HEARTBEAT_PERIOD_MS));
+
+    loop {
+        ticker.next().await;
+
+        // 先判断超时：上行队列满 (链路阻塞) 时也要及时触发失效保护
+        if ARMED.load(Ordering::Relaxed)
+            && state() == LinkState::Up
+            && since_last_rx().is_some_and(|d| d > timeout)
+        {
+            crate::fmt::warn!("Host link lost");
+            set_state(LinkState::Lost);
+        }
+
+        // 队列满时丢掉这次心跳，下一个周期再发
+        let uptime_s = Instant::now().as_secs() as u32;
+        let _ = UART_TX_CHANNEL.try_send(TxMessage::Heartbeat { uptime_s });
+    }
+}
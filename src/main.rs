@@ -1,15 +1,35 @@
 #![no_std]
 #![no_main]
 
+mod auth;
 mod bh1750;
 mod command;
 mod config;
+mod console;
+mod crypt;
+mod deadband;
 mod device_ui;
+mod dht;
 mod dht11;
+mod diag;
 mod fmt;
+mod frame;
+mod info;
+mod link;
+mod modbus;
 mod protocol;
+mod reliable;
+mod report;
+mod rtu;
+mod sampling;
+mod session;
 mod soil;
+mod stats;
+mod store;
+mod stream;
+mod txqueue;
 mod uart;
+mod uplink;
 
 use defmt::{error, info};
 #[cfg(not(feature = "defmt"))]
@@ -30,11 +50,16 @@ async fn main(spawner: Spawner) {
     // Initialization
     let config = config::stm_config();
     let p = embassy_stm32::init(config);
+    info::capture_reset_cause();
     let _receiver = config::CHANNEL_DHT11.receiver();
 
     // DHT11 Configuration (PA1)
     let mut dh11_pin = Flex::new(p.PA1);
     dh11_pin.set_as_input_output(Speed::VeryHigh);
+    // DHT 边沿时间戳使用 DWT 周期计数器，未连接调试器时也需手动开启
+    let mut core = cortex_m::Peripherals::take().unwrap();
+    core.DCB.enable_trace();
+    core.DWT.enable_cycle_counter();
 
     // ST7735 Configuration
     let mut spi_config = spi::Config::default();
@@ -64,7 +89,9 @@ async fn main(spawner: Spawner) {
     );
 
     // ADC for Soil Sensor
-    let adc = embassy_stm32::adc::Adc::new(p.ADC1);
+    let mut adc = embassy_stm32::adc::Adc::new(p.ADC1);
+    // 会话随机数借用 ADC 采集噪声，须在 DeviceInfo 发出前完成
+    session::init(&mut adc).await;
 
     // USART Configuration
     let mut _usart1_config = embassy_stm32::usart::Config::default();
@@ -81,7 +108,7 @@ async fn main(spawner: Spawner) {
         p.DMA1_CH5,
         _usart1_config,
     );
-    let mut usart = match usart {
+    let usart = match usart {
         Ok(val) => val,
         Err(e) => {
             error!("Failed to create Uart: {}", e);
@@ -95,20 +122,33 @@ async fn main(spawner: Spawner) {
     static LIGHT_CHANNEL: command::ActuatorChannel = command::ActuatorChannel::new();
     static BUZZER_CHANNEL: command::ActuatorChannel = command::ActuatorChannel::new();
 
-    usart.write(b"System Init...\r\n").await.unwrap();
     let (tx, rx) = usart.split();
+    // 上电通告，TX 任务启动后第一个发出
+    let _ = config::UART_TX_CHANNEL.try_send(protocol::TxMessage::DeviceInfo(info::device_info()));
     // 设置共享TX端 (已移除)
     // *SHARED_TX.lock().await = Some(tx);
 
+    // RS-485 收发器方向引脚 DE/RE (PA8)，空闲时为接收
+    let rs485_de = Output::new(p.PA8, Level::Low, Speed::VeryHigh);
+
     // Spawn UART Tasks
-    spawner.spawn(uart::uart_rx_task(rx)).unwrap();
-    spawner.spawn(uart::uart_tx_task(tx)).unwrap();
+    match config::LINK_PROTOCOL {
+        rtu::LinkProtocol::Frames => {
+            spawner.spawn(uart::uart_rx_task(rx)).unwrap();
+            spawner.spawn(uart::uart_tx_task(tx, rs485_de)).unwrap();
+        }
+        rtu::LinkProtocol::ModbusRtu => {
+            spawner.spawn(rtu::modbus_task(rx, tx, rs485_de)).unwrap();
+            spawner.spawn(rtu::discard_uplink_task()).unwrap();
+        }
+    }
 
     // Fan (High Trigger) - PB14
     spawner
         .spawn(command::actuator_task(
             Flex::new(p.PB14),
             FAN_CHANNEL.receiver(),
+            protocol::ActuatorTag::Fan,
             true,
         ))
         .unwrap();
@@ -117,6 +157,7 @@ async fn main(spawner: Spawner) {
         .spawn(command::actuator_task(
             Flex::new(p.PB12),
             PUMP_CHANNEL.receiver(),
+            protocol::ActuatorTag::Pump,
             true,
         ))
         .unwrap();
@@ -125,6 +166,7 @@ async fn main(spawner: Spawner) {
         .spawn(command::actuator_task(
             Flex::new(p.PB13),
             LIGHT_CHANNEL.receiver(),
+            protocol::ActuatorTag::Light,
             true,
         ))
         .unwrap();
@@ -133,6 +175,7 @@ async fn main(spawner: Spawner) {
         .spawn(command::actuator_task(
             Flex::new(p.PB15),
             BUZZER_CHANNEL.receiver(),
+            protocol::ActuatorTag::Buzzer,
             false,
         ))
         .unwrap();
@@ -171,5 +214,29 @@ async fn main(spawner: Spawner) {
         }
     }
 
+    // Spawn Snapshot Report Task
+    match spawner.spawn(report::snapshot_task()) {
+        Ok(_) => (),
+        Err(e) => {
+            error!("Failed to spawn snapshot_task: {}", e);
+        }
+    }
+
+    // Spawn Windowed Statistics Task
+    match spawner.spawn(report::stats_task()) {
+        Ok(_) => (),
+        Err(e) => {
+            error!("Failed to spawn stats_task: {}", e);
+        }
+    }
+
+    // Spawn Heartbeat / Link Monitor Task
+    match spawner.spawn(link::heartbeat_task()) {
+        Ok(_) => (),
+        Err(e) => {
+            error!("Failed to spawn heartbeat_task: {}", e);
+        }
+    }
+
     info!("System Initialized");
 }
@@ -0,0 +1,527 @@
+//! Modbus RTU 从站 (PDU 处理与 RTU 帧校验)
+//!
+//! 寄存器映射 (协议地址从 0 开始):
+//! - 输入寄存器 (FC 04): 0 土壤湿度, 1 温度 (0.01°C，有符号), 2 空气湿度, 3 光照强度,
+//!   4 有效标志 (bit0..3 依次表示以上读数是否已有数据，无数据的读数读出 0)
+//! - 线圈 (FC 01 / 05 / 0F): 0 Fan, 1 Pump, 2 Light, 3 Buzzer
+//! - 保持寄存器 (FC 03 / 06 / 10): 0..3 依次为各执行器的脉冲时长 (ms)，
+//!   写线圈为 ON 时按此时长打开，0 表示一直打开
+//!
+//! 写线圈只生成 `ControlCommand`，由调用方交给现有的命令任务执行，
+//! 命令被拒绝时调用方用 `Slave::reject` 把响应改写为异常响应；
+//! 线圈读出的是执行器的实际状态。
+
+use crate::protocol::{
+    AckMode, ActuatorStates, ActuatorTag, ControlCommand, NackReason, SensorSnapshot,
+};
+
+/// RTU 帧最大长度
+pub const MAX_ADU_LEN: usize = 256;
+/// 广播地址，执行写操作但不回复
+pub const BROADCAST: u8 = 0;
+
+/// 输入寄存器数量
+const INPUT_REGISTERS: u16 = 5;
+/// 线圈 / 保持寄存器数量，与执行器一一对应
+const ACTUATORS: u16 = ActuatorTag::ALL.len() as u16;
+
+/// 一次写多个线圈最多生成的命令数
+pub type Commands = heapless::Vec<ControlCommand, 4>;
+
+/// Modbus 异常码
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+#[allow(clippy::enum_variant_names)] // 与 Modbus 规范中的名称一致
+pub enum Exception {
+    IllegalFunction = 0x01,
+    IllegalDataAddress = 0x02,
+    IllegalDataValue = 0x03,
+    SlaveDeviceFailure = 0x04,
+    SlaveDeviceBusy = 0x06,
+}
+
+impl From<NackReason> for Exception {
+    /// 执行器忙对应 Slave Device Busy，其它拒绝原因 (如互锁) 对应 Slave Device Failure
+    fn from(reason: NackReason) -> Self {
+        match reason {
+            NackReason::Busy => Exception::SlaveDeviceBusy,
+            _ => Exception::SlaveDeviceFailure,
+        }
+    }
+}
+
+/// 处理请求时读取的设备状态
+pub struct ProcessImage {
+    pub sensors: SensorSnapshot,
+    pub actuators: ActuatorStates,
+}
+
+pub struct Slave {
+    address: u8,
+    /// 保持寄存器：各执行器的脉冲时长，按 `ActuatorTag::index()` 排列
+    pulse_ms: [u16; 4],
+}
+
+impl Slave {
+    pub const fn new(address: u8) -> Self {
+        Self {
+            address,
+            pulse_ms: [0; 4],
+        }
+    }
+
+    /// 处理一个 RTU 请求帧 (`ADDR PDU CRC_LO CRC_HI`)，响应帧写入 `response`
+    ///
+    /// 返回 None 表示 CRC 错误或不是发给本机的帧；`Some(0)` 表示广播帧，已执行但不回复。
+    pub fn handle(
+        &mut self,
+        request: &[u8],
+        image: &ProcessImage,
+        commands: &mut Commands,
+        response: &mut [u8],
+    ) -> Option<usize> {
+        let (&addr, pdu) = check_adu(request)?;
+        if addr != self.address && addr != BROADCAST {
+            return None;
+        }
+
+        let pdu_len = self.process_pdu(pdu, image, commands, &mut response[1..]);
+        if addr == BROADCAST {
+            return Some(0);
+        }
+
+        Some(self.finish(response, pdu_len))
+    }
+
+    /// 把 `handle` 写入的正常响应改写为异常响应，返回新的响应帧长度
+    ///
+    /// 用于写线圈生成的命令被命令任务拒绝的情况。
+    pub fn reject(&self, response: &mut [u8], exception: Exception) -> usize {
+        response[1] |= 0x80;
+        response[2] = exception as u8;
+        self.finish(response, 2)
+    }
+
+    /// 填写从站地址和 CRC，返回响应帧长度
+    fn finish(&self, response: &mut [u8], pdu_len: usize) -> usize {
+        response[0] = self.address;
+        let crc_idx = 1 + pdu_len;
+        let crc = crc16(&response[..crc_idx]);
+        response[crc_idx..crc_idx + 2].copy_from_slice(&crc.to_le_bytes());
+        crc_idx + 2
+    }
+
+    /// 处理一个请求 PDU，响应 PDU (正常或异常) 写入 `out`，返回其长度
+    pub fn process_pdu(
+        &mut self,
+        pdu: &[u8],
+        image: &ProcessImage,
+        commands: &mut Commands,
+        out: &mut [u8],
+    ) -> usize {
+        let function = pdu[0];
+        match self.dispatch(pdu, image, commands, out) {
+            Ok(len) => len,
+            Err(e) => {
+                out[0] = function | 0x80;
+                out[1] = e as u8;
+                2
+            }
+        }
+    }
+
+    fn dispatch(
+        &mut self,
+        pdu: &[u8],
+        image: &ProcessImage,
+        commands: &mut Commands,
+        out: &mut [u8],
+    ) -> Result<usize, Exception> {
+        let function = pdu[0];
+        out[0] = function;
+        match function {
+            // Read Coils
+            0x01 => {
+                let (start, qty) = read_range(pdu, 2000, ACTUATORS)?;
+                out[1] = qty.div_ceil(8) as u8;
+                out[2] = 0;
+                for i in 0..qty {
+                    if image.actuators.get(ActuatorTag::ALL[(start + i) as usize]) {
+                        out[2] |= 1 << i;
+                    }
+                }
+                Ok(3)
+            }
+            // Read Holding Registers
+            0x03 => {
+                let (start, qty) = read_range(pdu, 125, ACTUATORS)?;
+                let values = (start..start + qty).map(|i| self.pulse_ms[i as usize]);
+                Ok(put_registers(out, values))
+            }
+            // Read Input Registers
+            0x04 => {
+                let (start, qty) = read_range(pdu, 125, INPUT_REGISTERS)?;
+                let values = (start..start + qty).map(|i| input_register(&image.sensors, i));
+                Ok(put_registers(out, values))
+            }
+            // Write Single Coil
+            0x05 => {
+                let (addr, value) = fields(pdu, 5)?;
+                let on = match value {
+                    0xFF00 => true,
+                    0x0000 => false,
+                    _ => return Err(Exception::IllegalDataValue),
+                };
+                if addr >= ACTUATORS {
+                    return Err(Exception::IllegalDataAddress);
+                }
+                let _ = commands.push(self.coil_command(addr, on));
+                Ok(echo(pdu, out))
+            }
+            // Write Single Register
+            0x06 => {
+                let (addr, value) = fields(pdu, 5)?;
+                if addr >= ACTUATORS {
+                    return Err(Exception::IllegalDataAddress);
+                }
+                self.pulse_ms[addr as usize] = value;
+                Ok(echo(pdu, out))
+            }
+            // Write Multiple Coils
+            0x0F => {
+                let (start, qty) = write_range(pdu, 0x07B0, |qty| qty.div_ceil(8))?;
+                for i in 0..qty {
+                    let on = pdu[6 + (i / 8) as usize] & (1 << (i % 8)) != 0;
+                    let _ = commands.push(self.coil_command(start + i, on));
+                }
+                Ok(echo(&pdu[..5], out))
+            }
+            // Write Multiple Registers
+            0x10 => {
+                let (start, qty) = write_range(pdu, 123, |qty| qty * 2)?;
+                for i in 0..qty as usize {
+                    let value = u16::from_be_bytes([pdu[6 + 2 * i], pdu[7 + 2 * i]]);
+                    self.pulse_ms[start as usize + i] = value;
+                }
+                Ok(echo(&pdu[..5], out))
+            }
+            _ => Err(Exception::IllegalFunction),
+        }
+    }
+
+    /// 写线圈对应的执行器命令；ACK 交回调用方，由 Modbus 响应代替
+    fn coil_command(&self, coil: u16, on: bool) -> ControlCommand {
+        let actuator = ActuatorTag::ALL[coil as usize];
+        ControlCommand {
+            actuator,
+            state: on,
+            duration_ms: if on {
+                self.pulse_ms[actuator.index()]
+            } else {
+                0
+            },
+            seq: 0,
+            ack: AckMode::Modbus,
+        }
+    }
+}
+
+/// 校验 RTU 帧长度和 CRC，返回地址和 PDU
+fn check_adu(frame: &[u8]) -> Option<(&u8, &[u8])> {
+    // ADDR FC CRC_LO CRC_HI
+    if frame.len() < 4 || frame.len() > MAX_ADU_LEN {
+        return None;
+    }
+    let (content, crc) = frame.split_at(frame.len() - 2);
+    if crc16(content).to_le_bytes() != [crc[0], crc[1]] {
+        return None;
+    }
+    content.split_first()
+}
+
+/// 读取请求 PDU 中 FC 之后的两个 16 位字段，PDU 长度必须为 `len`
+fn fields(pdu: &[u8], len: usize) -> Result<(u16, u16), Exception> {
+    if pdu.len() != len {
+        return Err(Exception::IllegalDataValue);
+    }
+    Ok((
+        u16::from_be_bytes([pdu[1], pdu[2]]),
+        u16::from_be_bytes([pdu[3], pdu[4]]),
+    ))
+}
+
+/// 读请求的起始地址和数量，数量超出协议上限为非法值，超出寄存器范围为非法地址
+fn read_range(pdu: &[u8], max_qty: u16, count: u16) -> Result<(u16, u16), Exception> {
+    let (start, qty) = fields(pdu, 5)?;
+    if qty == 0 || qty > max_qty {
+        return Err(Exception::IllegalDataValue);
+    }
+    check_range(start, qty, count)?;
+    Ok((start, qty))
+}
+
+/// 写多个请求的起始地址和数量，并检查字节数与后续数据长度
+fn write_range(
+    pdu: &[u8],
+    max_qty: u16,
+    byte_count: fn(u16) -> u16,
+) -> Result<(u16, u16), Exception> {
+    if pdu.len() < 6 {
+        return Err(Exception::IllegalDataValue);
+    }
+    let (start, qty) = fields(&pdu[..5], 5)?;
+    let bytes = pdu[5] as usize;
+    if qty == 0 || qty > max_qty || bytes != byte_count(qty) as usize || pdu.len() != 6 + bytes {
+        return Err(Exception::IllegalDataValue);
+    }
+    check_range(start, qty, ACTUATORS)?;
+    Ok((start, qty))
+}
+
+fn check_range(start: u16, qty: u16, count: u16) -> Result<(), Exception> {
+    if start as u32 + qty as u32 > count as u32 {
+        Err(Exception::IllegalDataAddress)
+    } else {
+        Ok(())
+    }
+}
+
+fn input_register(sensors: &SensorSnapshot, addr: u16) -> u16 {
+    match addr {
+        0 => sensors.soil_moisture.unwrap_or(0),
+        1 => sensors.temperature.unwrap_or(0) as u16,
+        2 => sensors.humidity.unwrap_or(0),
+        3 => sensors.light_intensity.unwrap_or(0),
+        _ => [
+            sensors.soil_moisture.is_some(),
+            sensors.temperature.is_some(),
+            sensors.humidity.is_some(),
+            sensors.light_intensity.is_some(),
+        ]
+        .iter()
+        .enumerate()
+        .fold(0, |acc, (i, &valid)| acc | (u16::from(valid) << i)),
+    }
+}
+
+/// 写入 `FC BYTE_COUNT VALUES...`，返回长度
+fn put_registers(out: &mut [u8], values: impl Iterator<Item = u16>) -> usize {
+    let mut idx = 2;
+    for value in values {
+        out[idx..idx + 2].copy_from_slice(&value.to_be_bytes());
+        idx += 2;
+    }
+    out[1] = (idx - 2) as u8;
+    idx
+}
+
+fn echo(pdu: &[u8], out: &mut [u8]) -> usize {
+    out[..pdu.len()].copy_from_slice(pdu);
+    pdu.len()
+}
+
+/// CRC-16/MODBUS (poly 0xA001 反射, init 0xFFFF)，低字节在前发送
+pub fn crc16(data: &[u8]) -> u16 {
+    let mut crc: u16 = 0xFFFF;
+    for &byte in data {
+        crc ^= byte as u16;
+        for _ in 0..8 {
+            if crc & 1 != 0 {
+                crc = (crc >> 1) ^ 0xA001;
+            } else {
+                crc >>= 1;
+            }
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::ActuatorFeedback;
+
+    fn image() -> ProcessImage {
+        let mut sensors = SensorSnapshot::new();
+        sensors.temperature = Some(-250);
+        sensors.humidity = Some(4500);
+        let mut actuators = ActuatorStates::new();
+        actuators.update(ActuatorFeedback {
+            actuator: ActuatorTag::Pump,
+            state: true,
+            failsafe: false,
+            timestamp_ms: None,
+        });
+        ProcessImage { sensors, actuators }
+    }
+
+    /// 处理一个 PDU，返回响应 PDU 和生成的命令
+    fn pdu(slave: &mut Slave, request: &[u8]) -> (heapless::Vec<u8, 64>, Commands) {
+        let mut out = [0u8; 64];
+        let mut commands = Commands::new();
+        let len = slave.process_pdu(request, &image(), &mut commands, &mut out);
+        (heapless::Vec::from_slice(&out[..len]).unwrap(), commands)
+    }
+
+    #[test]
+    fn crc_check_value() {
+        assert_eq!(crc16(b"123456789"), 0x4B37);
+    }
+
+    #[test]
+    fn read_input_registers() {
+        let mut slave = Slave::new(1);
+        let (resp, _) = pdu(&mut slave, &[0x04, 0x00, 0x01, 0x00, 0x04]);
+        assert_eq!(
+            resp.as_slice(),
+            &[0x04, 0x08, 0xFF, 0x06, 0x11, 0x94, 0x00, 0x00, 0x00, 0x06]
+        );
+        // 超出映射范围
+        let (resp, _) = pdu(&mut slave, &[0x04, 0x00, 0x04, 0x00, 0x02]);
+        assert_eq!(resp.as_slice(), &[0x84, 0x02]);
+        // 数量为 0
+        let (resp, _) = pdu(&mut slave, &[0x04, 0x00, 0x00, 0x00, 0x00]);
+        assert_eq!(resp.as_slice(), &[0x84, 0x03]);
+    }
+
+    #[test]
+    fn read_coils() {
+        let mut slave = Slave::new(1);
+        let (resp, _) = pdu(&mut slave, &[0x01, 0x00, 0x00, 0x00, 0x04]);
+        assert_eq!(resp.as_slice(), &[0x01, 0x01, 0b0010]);
+        let (resp, _) = pdu(&mut slave, &[0x01, 0x00, 0x01, 0x00, 0x01]);
+        assert_eq!(resp.as_slice(), &[0x01, 0x01, 0b1]);
+    }
+
+    #[test]
+    fn write_coil_uses_pulse_register() {
+        let mut slave = Slave::new(1);
+        let (resp, _) = pdu(&mut slave, &[0x06, 0x00, 0x01, 0x13, 0x88]);
+        assert_eq!(resp.as_slice(), &[0x06, 0x00, 0x01, 0x13, 0x88]);
+        let (resp, _) = pdu(&mut slave, &[0x03, 0x00, 0x00, 0x00, 0x02]);
+        assert_eq!(resp.as_slice(), &[0x03, 0x04, 0x00, 0x00, 0x13, 0x88]);
+
+        let (resp, commands) = pdu(&mut slave, &[0x05, 0x00, 0x01, 0xFF, 0x00]);
+        assert_eq!(resp.as_slice(), &[0x05, 0x00, 0x01, 0xFF, 0x00]);
+        assert_eq!(
+            commands.as_slice(),
+            &[ControlCommand {
+                actuator: ActuatorTag::Pump,
+                state: true,
+                duration_ms: 5000,
+                seq: 0,
+                ack: AckMode::Modbus,
+            }]
+        );
+
+        let (resp, commands) = pdu(&mut slave, &[0x05, 0x00, 0x01, 0x12, 0x34]);
+        assert_eq!(resp.as_slice(), &[0x85, 0x03]);
+        assert!(commands.is_empty());
+        let (resp, _) = pdu(&mut slave, &[0x05, 0x00, 0x04, 0xFF, 0x00]);
+        assert_eq!(resp.as_slice(), &[0x85, 0x02]);
+    }
+
+    #[test]
+    fn write_multiple() {
+        let mut slave = Slave::new(1);
+        let (resp, _) = pdu(
+            &mut slave,
+            &[0x10, 0x00, 0x02, 0x00, 0x02, 0x04, 0x00, 0x64, 0x01, 0xF4],
+        );
+        assert_eq!(resp.as_slice(), &[0x10, 0x00, 0x02, 0x00, 0x02]);
+        assert_eq!(slave.pulse_ms, [0, 0, 100, 500]);
+
+        // Fan 关, Pump 关, Light 开, Buzzer 开
+        let (resp, commands) = pdu(&mut slave, &[0x0F, 0x00, 0x00, 0x00, 0x04, 0x01, 0b1100]);
+        assert_eq!(resp.as_slice(), &[0x0F, 0x00, 0x00, 0x00, 0x04]);
+        let states: heapless::Vec<(bool, u16), 4> =
+            commands.iter().map(|c| (c.state, c.duration_ms)).collect();
+        assert_eq!(
+            states.as_slice(),
+            &[(false, 0), (false, 0), (true, 100), (true, 500)]
+        );
+
+        // 字节数与数量不符
+        let (resp, commands) = pdu(
+            &mut slave,
+            &[0x0F, 0x00, 0x00, 0x00, 0x04, 0x02, 0x0C, 0x00],
+        );
+        assert_eq!(resp.as_slice(), &[0x8F, 0x03]);
+        assert!(commands.is_empty());
+    }
+
+    #[test]
+    fn rejected_coil_write_becomes_exception() {
+        let mut slave = Slave::new(0x11);
+        let mut commands = Commands::new();
+        let mut response = [0u8; MAX_ADU_LEN];
+        let mut request = [0x11, 0x05, 0x00, 0x01, 0xFF, 0x00, 0, 0];
+        let crc = crc16(&request[..6]).to_le_bytes();
+        request[6..].copy_from_slice(&crc);
+        slave
+            .handle(&request, &image(), &mut commands, &mut response)
+            .unwrap();
+
+        // 命令任务回复 Busy / Interlocked
+        let len = slave.reject(&mut response, NackReason::Busy.into());
+        assert_eq!(&response[..len - 2], &[0x11, 0x85, 0x06]);
+        assert_eq!(
+            crc16(&response[..len - 2]).to_le_bytes(),
+            [response[len - 2], response[len - 1]]
+        );
+        let len = slave.reject(&mut response, NackReason::Interlocked.into());
+        assert_eq!(&response[..len - 2], &[0x11, 0x85, 0x04]);
+    }
+
+    #[test]
+    fn unsupported_function() {
+        let mut slave = Slave::new(1);
+        let (resp, _) = pdu(&mut slave, &[0x2B, 0x0E, 0x01, 0x00]);
+        assert_eq!(resp.as_slice(), &[0xAB, 0x01]);
+    }
+
+    #[test]
+    fn rtu_addressing() {
+        let mut slave = Slave::new(0x11);
+        let image = image();
+        let mut commands = Commands::new();
+        let mut response = [0u8; MAX_ADU_LEN];
+
+        // 常见示例帧：读 0x11 号从站的保持寄存器
+        let request = [0x11, 0x03, 0x00, 0x00, 0x00, 0x01, 0x86, 0x9A];
+        assert_eq!(crc16(&request[..6]).to_le_bytes(), [0x86, 0x9A]);
+        let len = slave
+            .handle(&request, &image, &mut commands, &mut response)
+            .unwrap();
+        assert_eq!(&response[..len - 2], &[0x11, 0x03, 0x02, 0x00, 0x00]);
+        assert_eq!(
+            crc16(&response[..len - 2]).to_le_bytes(),
+            [response[len - 2], response[len - 1]]
+        );
+
+        // 其它从站、CRC 错误：不处理
+        let mut other = request;
+        other[0] = 0x12;
+        assert_eq!(
+            slave.handle(&other, &image, &mut commands, &mut response),
+            None
+        );
+        let mut corrupt = request;
+        corrupt[7] ^= 1;
+        assert_eq!(
+            slave.handle(&corrupt, &image, &mut commands, &mut response),
+            None
+        );
+
+        // 广播写线圈：执行但不回复
+        let mut broadcast = [0x00, 0x05, 0x00, 0x00, 0xFF, 0x00, 0, 0];
+        let crc = crc16(&broadcast[..6]).to_le_bytes();
+        broadcast[6..].copy_from_slice(&crc);
+        assert_eq!(
+            slave.handle(&broadcast, &image, &mut commands, &mut response),
+            Some(0)
+        );
+        assert_eq!(commands.len(), 1);
+    }
+}
@@ -1,5 +1,8 @@
 #![allow(dead_code)]
 
+use crate::auth::AuthError;
+use crate::console::Reply;
+
 /// 帧起始标志
 pub const SOF: u8 = 0xAA;
 
@@ -9,9 +12,21 @@ pub const SOF: u8 = 0xAA;
 pub enum MessageType {
     SensorReport = 0x01,
     ActuatorStatus = 0x02,
+    DeviceInfo = 0x03,  // 设备信息，上电时主动发送一次，也可由 GetDeviceInfo 查询
+    SensorStats = 0x04, // 传感器窗口统计 (最小、最大、平均、样本数)，每个统计窗口发送一次
     Command = 0x10,
     CommandAck = 0x11,
+    HostAck = 0x12, // 上位机确认可靠上行帧 (SEQ 为被确认帧的序号)
     Heartbeat = 0x20,
+    SecurityEvent = 0x21,  // 下位机拒绝了伪造或重放的帧
+    Diagnostics = 0x22,    // 链路诊断计数器，回复 GetDiagnostics
+    GetSensors = 0x30,     // 上位机查询所有传感器最新值，回复 SensorReport
+    GetActuators = 0x31,   // 上位机查询所有执行器状态，回复 ActuatorStatus
+    GetDeviceInfo = 0x32,  // 上位机查询设备信息，回复 DeviceInfo
+    GetDiagnostics = 0x33, // 上位机查询诊断计数器 (可同时清零)，回复 Diagnostics
+    Configure = 0x40,      // 上位机设置运行选项，逐项回复 CommandAck
+    SetInterval = 0x41,    // 上位机设置传感器采样间隔，逐项回复 CommandAck
+    SetDeadband = 0x42,    // 上位机设置传感器死区 (变化上报)，逐项回复 CommandAck
     Unknown = 0xFF,
 }
 
@@ -20,9 +35,21 @@ impl From<u8> for MessageType {
         match value {
             0x01 => MessageType::SensorReport,
             0x02 => MessageType::ActuatorStatus,
+            0x03 => MessageType::DeviceInfo,
+            0x04 => MessageType::SensorStats,
             0x10 => MessageType::Command,
             0x11 => MessageType::CommandAck,
+            0x12 => MessageType::HostAck,
             0x20 => MessageType::Heartbeat,
+            0x21 => MessageType::SecurityEvent,
+            0x22 => MessageType::Diagnostics,
+            0x30 => MessageType::GetSensors,
+            0x31 => MessageType::GetActuators,
+            0x32 => MessageType::GetDeviceInfo,
+            0x33 => MessageType::GetDiagnostics,
+            0x40 => MessageType::Configure,
+            0x41 => MessageType::SetInterval,
+            0x42 => MessageType::SetDeadband,
             _ => MessageType::Unknown,
         }
     }
@@ -38,6 +65,35 @@ pub enum SensorTag {
     LightIntensity = 0x04, // u16
 }
 
+impl SensorTag {
+    pub const ALL: [SensorTag; 4] = [
+        SensorTag::SoilMoisture,
+        SensorTag::Temperature,
+        SensorTag::Humidity,
+        SensorTag::LightIntensity,
+    ];
+
+    /// 在 `ALL` 中的下标，用于按传感器索引的数组
+    pub const fn index(self) -> usize {
+        self as usize - SensorTag::SoilMoisture as usize
+    }
+}
+
+impl TryFrom<u8> for SensorTag {
+    type Error = u8;
+
+    /// 未知 TAG 原样返回
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(SensorTag::SoilMoisture),
+            0x02 => Ok(SensorTag::Temperature),
+            0x03 => Ok(SensorTag::Humidity),
+            0x04 => Ok(SensorTag::LightIntensity),
+            other => Err(other),
+        }
+    }
+}
+
 /// 执行器 TAG 定义
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 #[repr(u8)]
@@ -48,18 +104,261 @@ pub enum ActuatorTag {
     Buzzer = 0x13,
 }
 
-impl From<u8> for ActuatorTag {
+impl TryFrom<u8> for ActuatorTag {
+    type Error = u8;
+
+    /// 未知 TAG 原样返回
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x10 => Ok(ActuatorTag::Fan),
+            0x11 => Ok(ActuatorTag::Pump),
+            0x12 => Ok(ActuatorTag::Light),
+            0x13 => Ok(ActuatorTag::Buzzer),
+            other => Err(other),
+        }
+    }
+}
+
+impl ActuatorTag {
+    pub const ALL: [ActuatorTag; 4] = [
+        ActuatorTag::Fan,
+        ActuatorTag::Pump,
+        ActuatorTag::Light,
+        ActuatorTag::Buzzer,
+    ];
+
+    /// 在 `ALL` 中的下标，用于按执行器索引的数组
+    pub const fn index(self) -> usize {
+        self as usize - ActuatorTag::Fan as usize
+    }
+}
+
+/// 命令被拒绝的原因，随 `CommandAck` (success = false) 上报
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum NackReason {
+    None = 0x00,        // 成功
+    UnknownTag = 0x01,  // 未知的执行器 TAG (Configure 中为未知的选项 TAG)
+    BadLength = 0x02,   // TLV 长度不是 1 (开关) 或 2 (脉冲)，或选项值长度错误
+    Busy = 0x03,        // 执行器命令队列已满
+    Interlocked = 0x04, // 与其它执行器互锁
+    OutOfRange = 0x05,  // 设置值超出允许范围 (如采样间隔低于传感器的最小值)
+}
+
+impl TryFrom<u8> for NackReason {
+    type Error = u8;
+
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x00 => Ok(NackReason::None),
+            0x01 => Ok(NackReason::UnknownTag),
+            0x02 => Ok(NackReason::BadLength),
+            0x03 => Ok(NackReason::Busy),
+            0x04 => Ok(NackReason::Interlocked),
+            0x05 => Ok(NackReason::OutOfRange),
+            other => Err(other),
+        }
+    }
+}
+
+/// ActuatorStatus TLV 的 FLAGS 字节：失效保护生效中
+pub const ACTUATOR_FLAG_FAILSAFE: u8 = 0x01;
+
+/// 心跳 Payload 中运行时间 TLV 的 TAG，值为上电以来的秒数 (u32)
+pub const HEARTBEAT_TAG_UPTIME: u8 = 0x01;
+
+/// SecurityEvent Payload 中的 TLV TAG
+pub const SECURITY_TAG_REASON: u8 = 0x01; // AuthError
+pub const SECURITY_TAG_COUNTER: u8 = 0x02; // 被拒绝帧的计数器 (u32)，未认证的帧没有
+
+/// 诊断计数器，Diagnostics Payload 中以此为 TAG，值均为 u32
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum DiagCounter {
+    RxCrcErrors = 0x01,      // 帧校验失败
+    RxResyncs = 0x02,        // 帧头 (版本或 LEN) 不合法或残帧超时，跳过重新同步
+    RxOverflows = 0x03,      // 帧长超过接收缓冲区，跳过重新同步
+    RxErrors = 0x04,         // 串口驱动接收错误 (噪声、帧错误、溢出等)
+    TxErrors = 0x05,         // 串口发送失败
+    TxRetransmits = 0x06,    // 可靠上行帧重传
+    TxGiveUps = 0x07,        // 可靠上行帧重传用尽被放弃
+    AuthFailures = 0x08,     // 认证失败被拒绝的帧
+    UiDrops = 0x09,          // 屏幕队列已满丢弃的消息
+    Commands = 0x0A,         // 收到的执行器命令 (帧、控制台、Modbus)
+    SensorOverwrites = 0x0B, // 传感器读数发出前被新读数覆盖
+}
+
+impl DiagCounter {
+    pub const ALL: [DiagCounter; 11] = [
+        DiagCounter::RxCrcErrors,
+        DiagCounter::RxResyncs,
+        DiagCounter::RxOverflows,
+        DiagCounter::RxErrors,
+        DiagCounter::TxErrors,
+        DiagCounter::TxRetransmits,
+        DiagCounter::TxGiveUps,
+        DiagCounter::AuthFailures,
+        DiagCounter::UiDrops,
+        DiagCounter::Commands,
+        DiagCounter::SensorOverwrites,
+    ];
+
+    /// 在 `ALL` 中的下标
+    pub fn index(self) -> usize {
+        self as usize - 1
+    }
+}
+
+impl TryFrom<u8> for DiagCounter {
+    type Error = u8;
+
+    /// 未知 TAG 原样返回
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        DiagCounter::ALL
+            .into_iter()
+            .find(|&c| c as u8 == value)
+            .ok_or(value)
+    }
+}
+
+/// 所有诊断计数器的值，按 `DiagCounter::index()` 索引
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Diagnostics {
+    pub counters: [u32; DiagCounter::ALL.len()],
+}
+
+impl Diagnostics {
+    pub fn get(&self, counter: DiagCounter) -> u32 {
+        self.counters[counter.index()]
+    }
+}
+
+/// GetDiagnostics Payload 中的 TLV TAG
+pub const DIAG_TAG_RESET: u8 = 0x01; // u8, 1 = 读出后清零
+
+/// 时间戳 TLV 的 TAG，值为采样时上电以来的毫秒数 (u32，约 49.7 天回绕)。
+/// 紧跟在它所标记的传感器或执行器 TLV 之后，仅在上位机开启时间戳选项后发送
+pub const TAG_TIMESTAMP: u8 = 0xF0;
+
+/// Configure 消息的选项 TAG
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[repr(u8)]
+pub enum ConfigTag {
+    Timestamps = 0x01,  // u8, 0 = 关闭, 1 = 开启上报时间戳
+    StatsWindow = 0x02, // u32, 统计窗口 (秒)，0 = 关闭窗口统计
+}
+
+impl TryFrom<u8> for ConfigTag {
+    type Error = u8;
+
+    /// 未知 TAG 原样返回
+    fn try_from(value: u8) -> Result<Self, Self::Error> {
+        match value {
+            0x01 => Ok(ConfigTag::Timestamps),
+            0x02 => Ok(ConfigTag::StatsWindow),
+            other => Err(other),
+        }
+    }
+}
+
+/// 一条运行选项设置
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigOption {
+    Timestamps(bool),
+    StatsWindow(u32),
+}
+
+impl ConfigOption {
+    pub fn tag(&self) -> ConfigTag {
+        match self {
+            ConfigOption::Timestamps(_) => ConfigTag::Timestamps,
+            ConfigOption::StatsWindow(_) => ConfigTag::StatsWindow,
+        }
+    }
+}
+
+/// 一条采样间隔设置 (SetInterval 消息)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SensorInterval {
+    pub sensor: SensorTag,
+    pub interval_ms: u32,
+}
+
+/// 一个传感器在一个统计窗口内的统计结果，数值为原始单位
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SensorStats {
+    pub sensor: SensorTag,
+    pub min: i32,
+    pub max: i32,
+    /// 四舍五入到原始单位
+    pub mean: i32,
+    pub count: u32,
+}
+
+/// 传感器死区：读数变化超过 `threshold` (原始单位) 或静默超过 `max_silence_ms` 时才上报
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Deadband {
+    pub threshold: u16,
+    /// 0 表示只按变化上报
+    pub max_silence_ms: u32,
+}
+
+/// 一条死区设置 (SetDeadband 消息)，`None` 表示取消死区、每次采样都上报
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SensorDeadband {
+    pub sensor: SensorTag,
+    pub band: Option<Deadband>,
+}
+
+/// DeviceInfo Payload 中的 TLV TAG
+pub const DEVICE_INFO_TAG_FIRMWARE: u8 = 0x01; // [MAJOR] [MINOR] [PATCH]
+pub const DEVICE_INFO_TAG_PROTOCOL: u8 = 0x02; // 支持的最高帧版本
+pub const DEVICE_INFO_TAG_UID: u8 = 0x03; // STM32 96 位唯一 ID (12 字节)
+pub const DEVICE_INFO_TAG_RESET_CAUSE: u8 = 0x04; // ResetCause
+pub const DEVICE_INFO_TAG_SENSORS: u8 = 0x05; // 已安装的传感器 TAG 列表
+pub const DEVICE_INFO_TAG_ACTUATORS: u8 = 0x06; // 已安装的执行器 TAG 列表
+pub const DEVICE_INFO_TAG_BOOT_NONCE: u8 = 0x07; // 本次上电的随机数，用于派生认证密钥和加密会话密钥
+
+/// 上次复位原因，来自 RCC_CSR 的复位标志
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[repr(u8)]
+pub enum ResetCause {
+    Unknown = 0x00,
+    PowerOn = 0x01,             // 上电/掉电复位 (PORRSTF)
+    Pin = 0x02,                 // NRST 引脚复位 (PINRSTF)
+    Software = 0x03,            // 软件复位 (SFTRSTF)
+    IndependentWatchdog = 0x04, // 独立看门狗 (IWDGRSTF)
+    WindowWatchdog = 0x05,      // 窗口看门狗 (WWDGRSTF)
+    LowPower = 0x06,            // 低功耗管理复位 (LPWRRSTF)
+}
+
+impl From<u8> for ResetCause {
     fn from(value: u8) -> Self {
         match value {
-            0x10 => ActuatorTag::Fan,
-            0x11 => ActuatorTag::Pump,
-            0x12 => ActuatorTag::Light,
-            0x13 => ActuatorTag::Buzzer,
-            _ => ActuatorTag::Fan, // 默认或错误处理
+            0x01 => ResetCause::PowerOn,
+            0x02 => ResetCause::Pin,
+            0x03 => ResetCause::Software,
+            0x04 => ResetCause::IndependentWatchdog,
+            0x05 => ResetCause::WindowWatchdog,
+            0x06 => ResetCause::LowPower,
+            _ => ResetCause::Unknown,
         }
     }
 }
 
+/// 设备信息 (下位机上报)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DeviceInfo {
+    pub firmware: [u8; 3], // MAJOR.MINOR.PATCH
+    pub protocol: u8,      // 支持的最高帧版本
+    pub uid: [u8; 12],
+    pub reset_cause: ResetCause,
+    pub sensors: &'static [SensorTag],
+    pub actuators: &'static [ActuatorTag],
+    pub boot_nonce: [u8; 8],
+}
+
 /// 通用的 TLV 结构用于构建 Payload
 #[derive(Debug)]
 pub struct TlvItem {
@@ -105,7 +404,7 @@ impl TlvItem {
 }
 
 /// 传感器上报数据结构 (内部使用)
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SensorData {
     SoilMoisture(u16),
     Temperature(i16),
@@ -113,31 +412,267 @@ pub enum SensorData {
     LightIntensity(u16),
 }
 
+impl SensorData {
+    pub fn tag(&self) -> SensorTag {
+        match self {
+            SensorData::SoilMoisture(_) => SensorTag::SoilMoisture,
+            SensorData::Temperature(_) => SensorTag::Temperature,
+            SensorData::Humidity(_) => SensorTag::Humidity,
+            SensorData::LightIntensity(_) => SensorTag::LightIntensity,
+        }
+    }
+}
+
+/// 带采样时间戳的传感器读数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SensorReading {
+    pub data: SensorData,
+    pub timestamp_ms: u32, // 采样时上电以来的毫秒数
+}
+
+/// 各传感器最新值快照，打包为一个多 TLV 的 SensorReport 帧
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SensorSnapshot {
+    pub soil_moisture: Option<u16>,
+    pub temperature: Option<i16>,
+    pub humidity: Option<u16>,
+    pub light_intensity: Option<u16>,
+    /// 各读数的采样时间戳，按 `SensorTag::index()` 索引
+    pub timestamps_ms: [Option<u32>; 4],
+}
+
+impl SensorSnapshot {
+    pub const fn new() -> Self {
+        Self {
+            soil_moisture: None,
+            temperature: None,
+            humidity: None,
+            light_intensity: None,
+            timestamps_ms: [None; 4],
+        }
+    }
+
+    /// 用一条带时间戳的读数更新对应字段
+    pub fn record(&mut self, reading: SensorReading) {
+        self.update(reading.data);
+        self.timestamps_ms[reading.data.tag().index()] = Some(reading.timestamp_ms);
+    }
+
+    /// 用一条读数更新对应字段
+    pub fn update(&mut self, data: SensorData) {
+        match data {
+            SensorData::SoilMoisture(v) => self.soil_moisture = Some(v),
+            SensorData::Temperature(v) => self.temperature = Some(v),
+            SensorData::Humidity(v) => self.humidity = Some(v),
+            SensorData::LightIntensity(v) => self.light_intensity = Some(v),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.readings().next().is_none()
+    }
+
+    /// 按 TAG 顺序遍历已有的读数
+    pub fn readings(&self) -> impl Iterator<Item = SensorData> {
+        [
+            self.soil_moisture.map(SensorData::SoilMoisture),
+            self.temperature.map(SensorData::Temperature),
+            self.humidity.map(SensorData::Humidity),
+            self.light_intensity.map(SensorData::LightIntensity),
+        ]
+        .into_iter()
+        .flatten()
+    }
+}
+
+/// 所有执行器的当前状态，按 `ActuatorTag::index()` 索引
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ActuatorStates {
+    on: [bool; 4],
+    failsafe: [bool; 4],
+    timestamps_ms: [Option<u32>; 4],
+}
+
+impl ActuatorStates {
+    pub const fn new() -> Self {
+        Self {
+            on: [false; 4],
+            failsafe: [false; 4],
+            timestamps_ms: [None; 4],
+        }
+    }
+
+    pub fn get(&self, actuator: ActuatorTag) -> bool {
+        self.on[actuator.index()]
+    }
+
+    /// 用一条状态反馈更新对应执行器
+    pub fn update(&mut self, feedback: ActuatorFeedback) {
+        let i = feedback.actuator.index();
+        self.on[i] = feedback.state;
+        self.failsafe[i] = feedback.failsafe;
+        self.timestamps_ms[i] = feedback.timestamp_ms;
+    }
+
+    /// 按 TAG 顺序遍历每个执行器的状态
+    pub fn feedbacks(&self) -> impl Iterator<Item = ActuatorFeedback> {
+        ActuatorTag::ALL
+            .map(|actuator| ActuatorFeedback {
+                actuator,
+                state: self.get(actuator),
+                failsafe: self.failsafe[actuator.index()],
+                timestamp_ms: self.timestamps_ms[actuator.index()],
+            })
+            .into_iter()
+    }
+}
+
 /// 执行器控制命令
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ControlCommand {
     pub actuator: ActuatorTag,
     pub state: bool,      // true = ON, false = OFF
     pub duration_ms: u16, // 0 = 永久, >0 = Pulse
+    pub seq: u8,          // 来源命令帧的序号，ACK 时回显
+    pub ack: AckMode,     // ACK 的回复方式
 }
 
-#[derive(Debug, Clone, Copy)]
+/// 命令执行结果 (CommandAck) 的回复方式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AckMode {
+    /// 不回复 (广播命令)
+    Silent,
+    /// CommandAck 帧
+    Frame,
+    /// 控制台文本
+    Text,
+    /// 交回 Modbus 从站任务 (写线圈)，命令被拒绝时回复异常响应
+    Modbus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct ActuatorFeedback {
     pub actuator: ActuatorTag,
     pub state: bool,
+    pub failsafe: bool,            // 链路丢失，状态由失效保护策略决定
+    pub timestamp_ms: Option<u32>, // 状态改变时上电以来的毫秒数
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub struct CommandAck {
-    pub actuator: ActuatorTag,
+    pub tag: u8, // 被确认命令的 TAG (未知 TAG 原样回显)
     pub success: bool,
+    pub reason: NackReason, // success = false 时的原因
+    pub seq: u8,            // 回显被确认命令的序号
+}
+
+impl CommandAck {
+    pub fn accepted(cmd: &ControlCommand) -> Self {
+        Self {
+            tag: cmd.actuator as u8,
+            success: true,
+            reason: NackReason::None,
+            seq: cmd.seq,
+        }
+    }
+
+    pub fn rejected(tag: u8, reason: NackReason, seq: u8) -> Self {
+        Self {
+            tag,
+            success: false,
+            reason,
+            seq,
+        }
+    }
+}
+
+/// 单个命令帧最多携带的命令条数
+pub const MAX_COMMANDS: usize = 8;
+
+/// 从串口收到并解码后的消息
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RxMessage {
+    SensorReport(SensorSnapshot),
+    ActuatorStatus(heapless::Vec<ActuatorFeedback, 4>),
+    /// 每条命令解码成功为 `Ok`，被拒绝时为 `Err(NACK)`
+    Command {
+        seq: u8,
+        commands: heapless::Vec<Result<ControlCommand, CommandAck>, MAX_COMMANDS>,
+    },
+    CommandAck(CommandAck),
+    /// 每条选项解码成功为 `Ok`，被拒绝时为 `Err(NACK)`
+    Configure {
+        seq: u8,
+        options: heapless::Vec<Result<ConfigOption, CommandAck>, MAX_COMMANDS>,
+    },
+    /// 每条设置解码成功为 `Ok`，被拒绝时为 `Err(NACK)`
+    SetInterval {
+        seq: u8,
+        intervals: heapless::Vec<Result<SensorInterval, CommandAck>, MAX_COMMANDS>,
+    },
+    /// 每条设置解码成功为 `Ok`，被拒绝时为 `Err(NACK)`
+    SetDeadband {
+        seq: u8,
+        deadbands: heapless::Vec<Result<SensorDeadband, CommandAck>, MAX_COMMANDS>,
+    },
+    HostAck {
+        seq: u8,
+    },
+    /// 上位机的心跳可以不带运行时间
+    Heartbeat {
+        uptime_s: Option<u32>,
+    },
+    SecurityEvent {
+        reason: AuthError,
+        counter: Option<u32>,
+    },
+    /// 上位机收到的 DeviceInfo，TAG 列表为解码后的副本
+    DeviceInfo {
+        firmware: [u8; 3],
+        protocol: u8,
+        uid: [u8; 12],
+        reset_cause: ResetCause,
+        sensors: heapless::Vec<SensorTag, 4>,
+        actuators: heapless::Vec<ActuatorTag, 4>,
+        boot_nonce: [u8; 8],
+    },
+    Diagnostics(Diagnostics),
+    /// 按 `SensorTag::index()` 排列，报文中没有的传感器为 `None`
+    SensorStats([Option<SensorStats>; SensorTag::ALL.len()]),
+    GetSensors,
+    GetActuators,
+    GetDeviceInfo,
+    GetDiagnostics {
+        reset: bool,
+    },
 }
 
 /// 发送到 UART TX 任务的统一消息枚举
 #[derive(Debug, Clone, Copy)]
 pub enum TxMessage {
-    Sensor(SensorData),
+    Sensor(SensorReading),
+    Snapshot(SensorSnapshot),
     Actuator(ActuatorFeedback),
+    ActuatorStates(ActuatorStates),
     Ack(CommandAck),
-    Heartbeat,
+    Heartbeat {
+        uptime_s: u32,
+    },
+    SecurityEvent {
+        reason: AuthError,
+        counter: Option<u32>,
+    },
+    DeviceInfo(DeviceInfo),
+    Diagnostics(Diagnostics),
+    /// 各传感器的窗口统计，按 `SensorTag::index()` 排列，窗口内没有样本的为 `None`
+    Stats([Option<SensorStats>; SensorTag::ALL.len()]),
+    /// 文本控制台的回复，不编码为帧
+    Console(Reply),
+}
+
+impl TxMessage {
+    /// 可靠模式下是否需要等待上位机确认 (`HostAck`)
+    pub fn needs_host_ack(&self) -> bool {
+        matches!(self, TxMessage::Actuator(_))
+    }
 }
@@ -0,0 +1,290 @@
+//! 上行可靠传输 (重传队列) 与下行命令的 ACK 缓存
+//!
+//! 关键上行消息 (如执行器状态反馈) 发出后等待上位机的 `HostAck`，
+//! 超时则以指数退避重传，超过重传次数后放弃。
+//! 时间以毫秒 (`u64`) 表示，由调用方传入。
+//!
+//! 上位机没收到 ACK 时会以相同 SEQ 重传命令帧，下位机不再执行，
+//! 而是原样重发第一次回复的 ACK (包括拒绝原因)。SEQ 相同但内容不同的帧
+//! (序号回绕、上位机重启) 是新命令，照常执行。
+
+use crate::frame::crc16_ccitt;
+use crate::protocol::{CommandAck, MAX_COMMANDS, TxMessage};
+
+/// 同时等待确认的最大帧数
+pub const MAX_PENDING: usize = 4;
+/// 最大重传次数 (不含首次发送)
+pub const MAX_RETRIES: u8 = 3;
+/// 首次等待确认的超时时间，之后每次重传翻倍
+pub const INITIAL_TIMEOUT_MS: u64 = 200;
+
+/// 等待确认的帧
+#[derive(Debug, Clone, Copy)]
+struct Pending {
+    seq: u8,
+    msg: TxMessage,
+    retries: u8,
+    deadline_ms: u64,
+}
+
+/// 超时处理结果
+#[derive(Debug, Clone, Copy)]
+pub enum RetryAction {
+    /// 需要以相同序号重传
+    Resend(u8, TxMessage),
+    /// 超过重传次数，放弃
+    GiveUp(u8),
+}
+
+pub struct RetransmitQueue {
+    slots: [Option<Pending>; MAX_PENDING],
+}
+
+impl RetransmitQueue {
+    pub const fn new() -> Self {
+        Self {
+            slots: [None; MAX_PENDING],
+        }
+    }
+
+    /// 登记一个已发送、等待确认的帧；队列已满时返回 false
+    ///
+    /// 执行器状态只有最新的有意义：同一执行器还在等待确认的旧状态不再重传，
+    /// 免得上位机确认了新状态后又收到迟到的旧状态。
+    pub fn track(&mut self, seq: u8, msg: TxMessage, now_ms: u64) -> bool {
+        if let TxMessage::Actuator(new) = msg {
+            for slot in self.slots.iter_mut() {
+                if let Some(Pending {
+                    msg: TxMessage::Actuator(old),
+                    ..
+                }) = slot
+                    && old.actuator == new.actuator
+                {
+                    *slot = None;
+                }
+            }
+        }
+        match self.slots.iter_mut().find(|slot| slot.is_none()) {
+            Some(slot) => {
+                *slot = Some(Pending {
+                    seq,
+                    msg,
+                    retries: 0,
+                    deadline_ms: now_ms + INITIAL_TIMEOUT_MS,
+                });
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// 收到上位机确认；返回该序号是否在等待中
+    pub fn ack(&mut self, seq: u8) -> bool {
+        for slot in self.slots.iter_mut() {
+            if matches!(slot, Some(p) if p.seq == seq) {
+                *slot = None;
+                return true;
+            }
+        }
+        false
+    }
+
+    /// 最早的超时时刻
+    pub fn next_deadline(&self) -> Option<u64> {
+        self.slots.iter().flatten().map(|p| p.deadline_ms).min()
+    }
+
+    /// 处理一个已到期的帧，没有到期帧时返回 None
+    pub fn poll(&mut self, now_ms: u64) -> Option<RetryAction> {
+        for slot in self.slots.iter_mut() {
+            let Some(p) = slot else { continue };
+            if p.deadline_ms > now_ms {
+                continue;
+            }
+            if p.retries >= MAX_RETRIES {
+                let action = RetryAction::GiveUp(p.seq);
+                *slot = None;
+                return Some(action);
+            }
+            p.retries += 1;
+            p.deadline_ms = now_ms + (INITIAL_TIMEOUT_MS << p.retries);
+            return Some(RetryAction::Resend(p.seq, p.msg));
+        }
+        None
+    }
+}
+
+impl Default for RetransmitQueue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// 最近一条命令帧的 SEQ、Payload 校验值及已回复的 ACK
+pub struct AckCache {
+    seq: Option<u8>,
+    digest: u16,
+    /// 这条命令帧总共要回复的 ACK 数
+    expected: usize,
+    acks: heapless::Vec<CommandAck, MAX_COMMANDS>,
+}
+
+impl AckCache {
+    pub const fn new() -> Self {
+        Self {
+            seq: None,
+            digest: 0,
+            expected: 0,
+            acks: heapless::Vec::new(),
+        }
+    }
+
+    /// 收到一条带序号的命令帧，`expected` 为它要回复的 ACK 数
+    ///
+    /// SEQ 和 Payload 都与上一条相同 (重传) 时返回 true；否则是新命令，清空缓存重新记录。
+    pub fn begin(&mut self, seq: u8, payload: &[u8], expected: usize) -> bool {
+        let digest = crc16_ccitt(payload);
+        if self.seq == Some(seq) && self.digest == digest {
+            return true;
+        }
+        self.seq = Some(seq);
+        self.digest = digest;
+        self.expected = expected;
+        self.acks.clear();
+        false
+    }
+
+    /// 这条命令帧的 ACK 是否都已回复 (执行器任务可能还没处理完)
+    pub fn complete(&self) -> bool {
+        self.acks.len() >= self.expected
+    }
+
+    /// 记录一条已发出的 ACK，不属于当前 SEQ 的忽略
+    pub fn record(&mut self, ack: CommandAck) {
+        if self.seq == Some(ack.seq) {
+            let _ = self.acks.push(ack);
+        }
+    }
+
+    /// 当前 SEQ 已回复的 ACK
+    pub fn acks(&self) -> &[CommandAck] {
+        &self.acks
+    }
+}
+
+impl Default for AckCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{ActuatorFeedback, ActuatorTag, NackReason};
+
+    const PUMP_ON: &[u8] = &[0x11, 0x01, 0x01];
+    const PUMP_OFF: &[u8] = &[0x11, 0x01, 0x00];
+
+    fn feedback() -> TxMessage {
+        TxMessage::Actuator(ActuatorFeedback {
+            actuator: ActuatorTag::Pump,
+            state: true,
+            failsafe: false,
+            timestamp_ms: None,
+        })
+    }
+
+    #[test]
+    fn ack_clears_pending() {
+        let mut q = RetransmitQueue::new();
+        assert!(q.track(5, feedback(), 0));
+        assert_eq!(q.next_deadline(), Some(INITIAL_TIMEOUT_MS));
+        assert!(q.ack(5));
+        assert!(!q.ack(5));
+        assert_eq!(q.next_deadline(), None);
+        assert!(q.poll(10_000).is_none());
+    }
+
+    #[test]
+    fn retransmits_with_backoff_then_gives_up() {
+        let mut q = RetransmitQueue::new();
+        q.track(9, feedback(), 0);
+        assert!(q.poll(199).is_none());
+
+        let mut now = 200;
+        for retry in 1..=MAX_RETRIES {
+            assert!(matches!(q.poll(now), Some(RetryAction::Resend(9, _))));
+            let timeout = INITIAL_TIMEOUT_MS << retry;
+            assert_eq!(q.next_deadline(), Some(now + timeout));
+            now += timeout;
+        }
+        assert!(matches!(q.poll(now), Some(RetryAction::GiveUp(9))));
+        assert_eq!(q.next_deadline(), None);
+    }
+
+    #[test]
+    fn full_queue_rejects() {
+        let mut q = RetransmitQueue::new();
+        for seq in 0..MAX_PENDING as u8 {
+            assert!(q.track(seq, TxMessage::Heartbeat { uptime_s: 0 }, 0));
+        }
+        assert!(!q.track(99, TxMessage::Heartbeat { uptime_s: 0 }, 0));
+    }
+
+    #[test]
+    fn duplicate_seq_replays_original_acks() {
+        let mut cache = AckCache::new();
+        assert!(!cache.begin(7, PUMP_ON, 1));
+        // 重传先于执行器任务的 ACK 到达
+        assert!(cache.begin(7, PUMP_ON, 1));
+        assert!(!cache.complete());
+
+        let busy = CommandAck::rejected(ActuatorTag::Pump as u8, NackReason::Busy, 7);
+        cache.record(busy);
+        // 其它序号的 ACK (如 Configure 的回复) 不混进来
+        cache.record(CommandAck::rejected(0, NackReason::BadLength, 8));
+        assert!(cache.complete());
+
+        assert!(cache.begin(7, PUMP_ON, 1));
+        assert_eq!(cache.acks(), &[busy]);
+
+        assert!(!cache.begin(8, PUMP_ON, 1));
+        assert!(cache.acks().is_empty());
+    }
+
+    #[test]
+    fn reused_seq_with_new_payload_is_new_command() {
+        let mut cache = AckCache::new();
+        assert!(!cache.begin(7, PUMP_ON, 1));
+        cache.record(CommandAck::rejected(
+            ActuatorTag::Pump as u8,
+            NackReason::Busy,
+            7,
+        ));
+
+        // SEQ 回绕或上位机重启后复用了序号
+        assert!(!cache.begin(7, PUMP_OFF, 1));
+        assert!(cache.acks().is_empty());
+        assert!(!cache.complete());
+    }
+
+    #[test]
+    fn newer_feedback_replaces_pending_one() {
+        let off = TxMessage::Actuator(ActuatorFeedback {
+            actuator: ActuatorTag::Pump,
+            state: false,
+            failsafe: false,
+            timestamp_ms: None,
+        });
+
+        let mut q = RetransmitQueue::new();
+        q.track(1, feedback(), 0);
+        q.track(2, off, 10);
+        // 只确认了 OFF，迟到的 ON 不会再重传
+        assert!(q.ack(2));
+        assert!(!q.ack(1));
+        assert_eq!(q.next_deadline(), None);
+        assert!(q.poll(10_000).is_none());
+    }
+}
@@ -0,0 +1,168 @@
+//! 传感器数据上报
+//!
+//! 各传感器任务通过 `publish` 发布读数：更新最新值存储、转发给 UI，
+//! 并按 `config::SENSOR_REPORT_MODE` 决定是逐条上报还是由 `snapshot_task` 定期打包上报。
+//! 发布从不等待：上行队列只保留每个传感器的最新读数，链路阻塞时旧读数被覆盖，采样照常进行。
+//! 设置了死区的传感器只在读数变化超过死区或静默太久时才进入上行队列 (见 `deadband`)。
+//! 每个读数 (不论是否上报) 都计入窗口统计，`stats_task` 在每个窗口结束时发送 SensorStats。
+
+use crate::config::{
+    SENSOR_DEADBANDS, SENSOR_REPORT_MODE, SNAPSHOT_PERIOD_MS, STATS_WINDOW_S, UART_TX_CHANNEL,
+    UI_CHANNEL,
+};
+use crate::deadband::{self, ChangeFilter};
+use crate::diag;
+use crate::protocol::{Deadband, SensorData, SensorReading, SensorTag, TxMessage};
+use crate::sampling;
+use crate::stats::Accumulator;
+use crate::store;
+use core::cell::RefCell;
+use core::sync::atomic::{AtomicU32, Ordering};
+use embassy_executor::task;
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+
+/// 传感器上报模式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ReportMode {
+    /// 每条读数单独一帧
+    PerReading,
+    /// 定期把所有传感器的最新值打包为一帧
+    Snapshot,
+}
+
+/// 各传感器的死区设置和上一次上报，按 `SensorTag::index()` 排列
+struct Filters {
+    bands: [Option<Deadband>; SensorTag::ALL.len()],
+    filters: [ChangeFilter; SensorTag::ALL.len()],
+}
+
+static FILTERS: Mutex<CriticalSectionRawMutex, RefCell<Filters>> =
+    Mutex::new(RefCell::new(Filters {
+        bands: SENSOR_DEADBANDS,
+        filters: [ChangeFilter::new(); SensorTag::ALL.len()],
+    }));
+
+/// 修改传感器死区，下一次采样起生效
+pub fn set_deadband(sensor: SensorTag, band: Option<Deadband>) {
+    FILTERS.lock(|f| f.borrow_mut().bands[sensor.index()] = band);
+}
+
+/// 当前统计窗口内各传感器的累计值，按 `SensorTag::index()` 排列
+static STATS: Mutex<CriticalSectionRawMutex, RefCell<[Accumulator; SensorTag::ALL.len()]>> =
+    Mutex::new(RefCell::new([Accumulator::new(); SensorTag::ALL.len()]));
+
+/// 统计窗口长度 (s)，0 = 关闭
+static STATS_WINDOW: AtomicU32 = AtomicU32::new(STATS_WINDOW_S);
+/// 窗口长度被修改，`stats_task` 丢弃当前窗口重新开始
+static STATS_WINDOW_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 修改统计窗口长度 (s)，0 = 关闭；当前窗口作废，立即开始新窗口
+pub fn set_stats_window(seconds: u32) {
+    STATS_WINDOW.store(seconds, Ordering::Relaxed);
+    STATS_WINDOW_CHANGED.signal(());
+}
+
+/// 这些读数中是否有需要上报的；需要时把它们全部记为已上报
+fn due<I: Iterator<Item = SensorData>>(readings: impl Fn() -> I, now_ms: u32) -> bool {
+    FILTERS.lock(|f| {
+        let mut f = f.borrow_mut();
+        let due = readings().any(|data| {
+            let i = data.tag().index();
+            f.filters[i].due(deadband::value(data), now_ms, f.bands[i])
+        });
+        if due {
+            for data in readings() {
+                f.filters[data.tag().index()].reported(deadband::value(data), now_ms);
+            }
+        }
+        due
+    })
+}
+
+/// 发布一条传感器读数，应在采样后立即调用 (时间戳取调用时刻)
+pub fn publish(data: SensorData) {
+    let reading = SensorReading {
+        data,
+        timestamp_ms: Instant::now().as_millis() as u32,
+    };
+    store::update_sensor(reading);
+    STATS.lock(|s| s.borrow_mut()[data.tag().index()].add(deadband::value(data)));
+
+    let msg = TxMessage::Sensor(reading);
+    if SENSOR_REPORT_MODE == ReportMode::PerReading
+        && due(|| core::iter::once(data), reading.timestamp_ms)
+    {
+        // 传感器上报不会被退回
+        let _ = UART_TX_CHANNEL.try_send(msg);
+    }
+    if UI_CHANNEL.try_send(msg).is_err() {
+        diag::incr(&diag::UI_DROPS);
+    }
+}
+
+/// 快照模式下定期上报所有传感器的最新值
+///
+/// 周期取 `SNAPSHOT_PERIOD_MS` 与最短采样间隔中的较大者：没有新读数时发快照没有意义，
+/// 上位机用 SetInterval 放慢采样时快照也随之放慢。
+#[task]
+pub async fn snapshot_task() {
+    if SENSOR_REPORT_MODE != ReportMode::Snapshot {
+        return;
+    }
+
+    let mut since = Instant::now();
+    loop {
+        let period_ms = SNAPSHOT_PERIOD_MS.max(sampling::shortest_interval_ms().into());
+        let deadline = since + Duration::from_millis(period_ms);
+        if let Either::Second(()) = select(Timer::at(deadline), sampling::interval_changed()).await
+        {
+            // 采样间隔变了，按新周期重新计算截止时刻
+            continue;
+        }
+        since = deadline;
+
+        let snapshot = store::sensors();
+        // 任一传感器需要上报时发送整个快照
+        let now_ms = Instant::now().as_millis() as u32;
+        if !snapshot.is_empty() && due(|| snapshot.readings(), now_ms) {
+            let _ = UART_TX_CHANNEL.try_send(TxMessage::Snapshot(snapshot));
+        }
+    }
+}
+
+/// 每个统计窗口结束时发送各传感器的最小、最大、平均值和样本数
+#[task]
+pub async fn stats_task() {
+    loop {
+        let window_s = STATS_WINDOW.load(Ordering::Relaxed);
+        let changed = if window_s == 0 {
+            STATS_WINDOW_CHANGED.wait().await;
+            true
+        } else {
+            let window = Timer::after(Duration::from_secs(window_s.into()));
+            matches!(
+                select(window, STATS_WINDOW_CHANGED.wait()).await,
+                Either::Second(())
+            )
+        };
+        if changed {
+            // 窗口长度被修改：丢弃已累计的样本，开始新窗口
+            STATS.lock(|s| s.take());
+            continue;
+        }
+
+        // 取出累计值的同时开始下一个窗口
+        let stats = STATS.lock(|s| {
+            let s = s.take();
+            SensorTag::ALL.map(|tag| s[tag.index()].finish(tag))
+        });
+        // 窗口内没有任何样本 (传感器全部故障) 时不发送
+        if stats.iter().any(Option::is_some) {
+            UART_TX_CHANNEL.send(TxMessage::Stats(stats)).await;
+        }
+    }
+}
@@ -0,0 +1,94 @@
+//! Modbus RTU 从站任务
+//!
+//! `config::LINK_PROTOCOL` 选择 Modbus RTU 时代替 `uart` 模块的帧收发任务，
+//! 寄存器映射与 PDU 处理见 `modbus` 模块。写线圈生成的命令经 `COMMAND_CHANNEL` 交给现有的执行器任务，
+//! 命令任务经 `MODBUS_ACK_CHANNEL` 交回 ACK，命令被拒绝时回复异常响应。
+
+use crate::config::{COMMAND_CHANNEL, MODBUS_ACK_CHANNEL, MODBUS_SLAVE_ADDR, UART_TX_CHANNEL};
+use crate::diag;
+use crate::link;
+use crate::modbus::{Commands, Exception, MAX_ADU_LEN, ProcessImage, Slave};
+use crate::store;
+use crate::uart::Port;
+use embassy_executor::task;
+use embassy_stm32::{
+    gpio::Output,
+    mode::Async,
+    usart::{UartRx, UartTx},
+};
+
+/// 串口链路协议
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[allow(dead_code)] // 只有 config 中选中的变体会被构造
+pub enum LinkProtocol {
+    /// 自定义 `0xAA` 帧 (见通信协议文档)
+    Frames,
+    /// Modbus RTU 从站
+    ModbusRtu,
+}
+
+#[task]
+pub async fn modbus_task(
+    mut rx: UartRx<'static, Async>,
+    tx: UartTx<'static, Async>,
+    de: Output<'static>,
+) {
+    let mut port = Port::new(tx, de);
+    let mut slave = Slave::new(MODBUS_SLAVE_ADDR);
+    let mut request = [0u8; MAX_ADU_LEN];
+    let mut response = [0u8; MAX_ADU_LEN];
+
+    loop {
+        // 以空闲线路作为帧间隔
+        let len = match rx.read_until_idle(&mut request).await {
+            Ok(len) => len,
+            Err(e) => {
+                crate::fmt::warn!("Modbus RX Error: {}", e);
+                diag::incr(&diag::RX_ERRORS);
+                continue;
+            }
+        };
+
+        let image = ProcessImage {
+            sensors: store::sensors(),
+            actuators: store::actuators(),
+        };
+        let mut commands = Commands::new();
+        let Some(resp_len) = slave.handle(&request[..len], &image, &mut commands, &mut response)
+        else {
+            // CRC 错误或发给其它从站
+            continue;
+        };
+
+        // Modbus 主站循环轮询，轮询本身就起到心跳的作用
+        link::arm();
+        link::frame_received();
+        let mut rejected = None;
+        for cmd in commands {
+            COMMAND_CHANNEL.send(cmd).await;
+            let ack = MODBUS_ACK_CHANNEL.receive().await;
+            if !ack.success {
+                rejected.get_or_insert(ack.reason);
+            }
+        }
+        // 有命令被拒绝 (互锁、忙) 时不回显请求，改为回复异常响应
+        let resp_len = match rejected {
+            Some(reason) if resp_len > 0 => slave.reject(&mut response, Exception::from(reason)),
+            _ => resp_len,
+        };
+        if resp_len > 0
+            && let Err(e) = port.write(&response[..resp_len]).await
+        {
+            crate::fmt::warn!("Modbus TX Error: {}", e);
+            diag::incr(&diag::TX_ERRORS);
+        }
+    }
+}
+
+/// Modbus 模式下上行消息 (传感器上报、心跳等) 没有去处，丢弃以免发送方阻塞
+#[task]
+pub async fn discard_uplink_task() {
+    loop {
+        let _ = UART_TX_CHANNEL.receive().await;
+    }
+}
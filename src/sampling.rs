@@ -0,0 +1,88 @@
+//! 传感器采样间隔
+//!
+//! 各传感器任务每次采样后调用 `wait`。间隔可由上位机通过 SetInterval 在运行时修改，
+//! 正在等待的任务会立即按新间隔重新计算下一次采样时刻，无需重启。
+//! 快照模式的上报周期也跟随最短的采样间隔 (见 `report::snapshot_task`)。
+
+use crate::config::{
+    BH1750_INTERVAL_MS, BH1750_MIN_INTERVAL_MS, DHT_INTERVAL_MS, DHT_VARIANT, SOIL_INTERVAL_MS,
+    SOIL_MIN_INTERVAL_MS,
+};
+use crate::protocol::{NackReason, SensorTag};
+use core::sync::atomic::{AtomicU32, Ordering};
+use embassy_futures::select::{Either, select};
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer};
+
+/// 当前采样间隔 (ms)，按 `SensorTag::index()` 排列
+static INTERVALS: [AtomicU32; SensorTag::ALL.len()] = [
+    AtomicU32::new(SOIL_INTERVAL_MS),
+    AtomicU32::new(DHT_INTERVAL_MS),
+    AtomicU32::new(DHT_INTERVAL_MS),
+    AtomicU32::new(BH1750_INTERVAL_MS),
+];
+
+/// 间隔被修改的通知，每个传感器只有一个任务在等待
+static CHANGED: [Signal<CriticalSectionRawMutex, ()>; SensorTag::ALL.len()] =
+    [const { Signal::new() }; SensorTag::ALL.len()];
+/// 任一间隔被修改的通知，由快照任务等待
+static ANY_CHANGED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
+
+/// 同一个器件测量的传感器共用一个间隔 (温湿度都来自同一个 DHT)
+fn siblings(sensor: SensorTag) -> &'static [SensorTag] {
+    match sensor {
+        SensorTag::Temperature | SensorTag::Humidity => {
+            &[SensorTag::Temperature, SensorTag::Humidity]
+        }
+        SensorTag::SoilMoisture => &[SensorTag::SoilMoisture],
+        SensorTag::LightIntensity => &[SensorTag::LightIntensity],
+    }
+}
+
+fn min_interval_ms(sensor: SensorTag) -> u32 {
+    match sensor {
+        SensorTag::SoilMoisture => SOIL_MIN_INTERVAL_MS,
+        SensorTag::Temperature | SensorTag::Humidity => DHT_VARIANT.min_interval_ms(),
+        SensorTag::LightIntensity => BH1750_MIN_INTERVAL_MS,
+    }
+}
+
+/// 修改采样间隔，低于传感器的最小间隔时拒绝
+pub fn set_interval(sensor: SensorTag, interval_ms: u32) -> Result<(), NackReason> {
+    if interval_ms < min_interval_ms(sensor) {
+        return Err(NackReason::OutOfRange);
+    }
+    for &s in siblings(sensor) {
+        INTERVALS[s.index()].store(interval_ms, Ordering::Relaxed);
+        CHANGED[s.index()].signal(());
+    }
+    ANY_CHANGED.signal(());
+    Ok(())
+}
+
+/// 所有传感器中最短的采样间隔 (ms)
+pub fn shortest_interval_ms() -> u32 {
+    INTERVALS
+        .iter()
+        .map(|interval| interval.load(Ordering::Relaxed))
+        .fold(u32::MAX, u32::min)
+}
+
+/// 等到任一传感器的采样间隔被修改
+pub async fn interval_changed() {
+    ANY_CHANGED.wait().await
+}
+
+/// 等到距上一次采样开始 (`since`) 满一个采样间隔
+pub async fn wait(sensor: SensorTag, since: Instant) {
+    loop {
+        let interval_ms = INTERVALS[sensor.index()].load(Ordering::Relaxed);
+        let deadline = since + Duration::from_millis(interval_ms.into());
+        match select(Timer::at(deadline), CHANGED[sensor.index()].wait()).await {
+            Either::First(()) => return,
+            // 间隔变了，按新间隔重新计算截止时刻
+            Either::Second(()) => {}
+        }
+    }
+}
@@ -0,0 +1,89 @@
+//! 加密会话 (上电随机数与会话密钥)
+//!
+//! 上电时从 ADC 噪声采集随机数，与预置密钥一起派生本次上电的会话密钥 (加密) 和认证密钥。
+//! 随机数随 DeviceInfo 明文上报，上位机用同样的方法派生密钥。
+//!
+//! 帧计数器每次上电从头开始，随机数一旦重复就会重用 ChaCha20 密钥流，
+//! 因此随机数还混入了保存在备份寄存器中的上电计数器，保证每次复位都不同。
+
+use crate::auth::{self, BOOT_NONCE_LEN, sha256};
+use crate::config::AUTH_KEY;
+use crate::crypt::derive_key;
+use embassy_stm32::adc::{Adc, SampleTime};
+use embassy_stm32::pac;
+use embassy_stm32::peripherals::ADC1;
+use embassy_sync::once_lock::OnceLock;
+use embassy_time::Instant;
+
+struct Session {
+    boot_nonce: [u8; BOOT_NONCE_LEN],
+    key: [u8; 32],
+    auth_key: [u8; auth::KEY_LEN],
+}
+
+static SESSION: OnceLock<Session> = OnceLock::new();
+
+/// 采样次数，每次取 ADC 读数和当前时钟的低 8 位
+const ENTROPY_SAMPLES: usize = 64;
+
+/// 上电计数器的低 16 位所在的备份寄存器 (BKP_DR1)，高 16 位在下一个 (BKP_DR2)
+const BOOT_COUNT_DR: usize = 0;
+
+/// 读出并递增上电计数器
+///
+/// 备份寄存器在复位时保持，VBAT 接了电池时断电也保持；没有电池时断电后从 0 重新计数，
+/// 此时随机数的唯一性仍由 ADC 噪声保证。
+fn next_boot_count() -> u32 {
+    pac::RCC.apb1enr().modify(|w| {
+        w.set_pwren(true);
+        w.set_bkpen(true);
+    });
+    // 解除备份域写保护
+    pac::PWR.cr().modify(|w| w.set_dbp(true));
+
+    let (lo, hi) = (pac::BKP.dr(BOOT_COUNT_DR), pac::BKP.dr(BOOT_COUNT_DR + 1));
+    let count = (u32::from(hi.read().d()) << 16 | u32::from(lo.read().d())).wrapping_add(1);
+    lo.write(|w| w.set_d(count as u16));
+    hi.write(|w| w.set_d((count >> 16) as u16));
+    count
+}
+
+/// 生成上电随机数并派生会话密钥，必须在 UART 任务启动前调用
+pub async fn init(adc: &mut Adc<'static, ADC1>) {
+    // 内部温度传感器在最短采样时间下的最低位基本是噪声
+    let mut temperature = adc.enable_temperature();
+    adc.set_sample_time(SampleTime::CYCLES1_5);
+
+    // UID 区分芯片，上电计数器区分同一芯片的各次上电，其余为噪声
+    let mut pool = [0u8; 16 + ENTROPY_SAMPLES * 2];
+    pool[..12].copy_from_slice(embassy_stm32::uid::uid());
+    pool[12..16].copy_from_slice(&next_boot_count().to_be_bytes());
+    for i in 0..ENTROPY_SAMPLES {
+        let sample = adc.read(&mut temperature).await;
+        pool[16 + 2 * i] = sample as u8;
+        pool[17 + 2 * i] = Instant::now().as_ticks() as u8;
+    }
+
+    let digest = sha256(&pool);
+    let mut boot_nonce = [0u8; BOOT_NONCE_LEN];
+    boot_nonce.copy_from_slice(&digest[..BOOT_NONCE_LEN]);
+    let _ = SESSION.init(Session {
+        boot_nonce,
+        key: derive_key(&AUTH_KEY, &boot_nonce),
+        auth_key: auth::derive_key(&AUTH_KEY, &boot_nonce),
+    });
+}
+
+pub fn boot_nonce() -> [u8; BOOT_NONCE_LEN] {
+    SESSION.try_get().map(|s| s.boot_nonce).unwrap_or_default()
+}
+
+/// 本次上电的会话密钥，`init` 之前为 None
+pub fn key() -> Option<&'static [u8; 32]> {
+    SESSION.try_get().map(|s| &s.key)
+}
+
+/// 本次上电的认证密钥，`init` 之前为 None
+pub fn auth_key() -> Option<&'static [u8; auth::KEY_LEN]> {
+    SESSION.try_get().map(|s| &s.auth_key)
+}
@@ -1,19 +1,18 @@
-use defmt;
+use crate::protocol::SensorTag;
+use crate::sampling;
 use embassy_stm32::{
     adc::Adc,
     peripherals::{ADC1, PA0},
 };
-use embassy_time::{Duration, Timer};
+use embassy_time::Instant;
 
 #[embassy_executor::task]
 pub async fn soil(mut adc: Adc<'static, ADC1>, mut pin: embassy_stm32::Peri<'static, PA0>) {
     use embassy_stm32::adc::SampleTime;
-    let tx_sender = crate::config::UART_TX_CHANNEL.sender();
     adc.set_sample_time(SampleTime::CYCLES239_5);
 
-    let ui_sender = crate::config::UI_CHANNEL.sender();
-
     loop {
+        let started = Instant::now();
         defmt::info!("Starting soil read...");
         let mut v = adc.read(&mut pin).await;
 
@@ -23,11 +22,8 @@ pub async fn soil(mut adc: Adc<'static, ADC1>, mut pin: embassy_stm32::Peri<'sta
         defmt::info!("Soil moisture: {}", v);
 
         // API 定义 SoilMoisture 为 u16
-        let report =
-            crate::protocol::TxMessage::Sensor(crate::protocol::SensorData::SoilMoisture(v));
-        tx_sender.send(report.clone()).await;
-        let _ = ui_sender.try_send(report);
+        crate::report::publish(crate::protocol::SensorData::SoilMoisture(v));
 
-        Timer::after(Duration::from_secs(1)).await;
+        sampling::wait(SensorTag::SoilMoisture, started).await;
     }
 }
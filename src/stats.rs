@@ -0,0 +1,119 @@
+//! 传感器窗口统计
+//!
+//! 每个传感器在一个统计窗口内累计最小值、最大值、平均值和样本数，窗口结束时打包为 SensorStats 上报。
+//! 全部用整数运算：读数本身就是定点数 (如 0.01°C)，平均值四舍五入到同样的单位。
+
+use crate::protocol::{SensorStats, SensorTag};
+
+/// 读数加上此偏移后为非负数 (i16 与 u16 的并集)，累加和用无符号数
+const OFFSET: i32 = 1 << 15;
+
+/// 一个传感器在当前窗口内的累计值，全零即为空窗口
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Accumulator {
+    min: i32,
+    max: i32,
+    /// 读数加 `OFFSET` 后的和
+    sum: u64,
+    count: u32,
+}
+
+impl Accumulator {
+    pub const fn new() -> Self {
+        Self {
+            min: 0,
+            max: 0,
+            sum: 0,
+            count: 0,
+        }
+    }
+
+    /// 累计一个读数 (原始单位)
+    pub fn add(&mut self, value: i32) {
+        if self.count == 0 {
+            self.min = value;
+            self.max = value;
+        } else {
+            self.min = self.min.min(value);
+            self.max = self.max.max(value);
+        }
+        self.sum += (value + OFFSET) as u64;
+        self.count = self.count.saturating_add(1);
+    }
+
+    /// 本窗口的统计结果，没有样本时返回 `None`
+    pub fn finish(&self, sensor: SensorTag) -> Option<SensorStats> {
+        if self.count == 0 {
+            return None;
+        }
+        let count = u64::from(self.count);
+        // 四舍五入
+        let mean = (self.sum + count / 2) / count;
+        Some(SensorStats {
+            sensor,
+            min: self.min,
+            max: self.max,
+            mean: mean as i32 - OFFSET,
+            count: self.count,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn empty_window_has_no_stats() {
+        assert_eq!(Accumulator::new().finish(SensorTag::Humidity), None);
+    }
+
+    #[test]
+    fn min_max_mean_count() {
+        let mut acc = Accumulator::new();
+        for v in [4500, 4600, 4550, 4401] {
+            acc.add(v);
+        }
+        assert_eq!(
+            acc.finish(SensorTag::Humidity),
+            Some(SensorStats {
+                sensor: SensorTag::Humidity,
+                min: 4401,
+                max: 4600,
+                // 18051 / 4 = 4512.75
+                mean: 4513,
+                count: 4,
+            })
+        );
+    }
+
+    #[test]
+    fn negative_temperatures_round_to_nearest() {
+        let mut acc = Accumulator::new();
+        for v in [-250, -251] {
+            acc.add(v);
+        }
+        let stats = acc.finish(SensorTag::Temperature).unwrap();
+        assert_eq!((stats.min, stats.max), (-251, -250));
+        // -250.5 四舍五入为 -250
+        assert_eq!(stats.mean, -250);
+
+        acc.add(-260);
+        // -761 / 3 = -253.67
+        assert_eq!(acc.finish(SensorTag::Temperature).unwrap().mean, -254);
+    }
+
+    #[test]
+    fn full_range_values_do_not_overflow() {
+        let mut acc = Accumulator::new();
+        for _ in 0..100_000 {
+            acc.add(u16::MAX.into());
+        }
+        acc.add(i16::MIN.into());
+        let stats = acc.finish(SensorTag::LightIntensity).unwrap();
+        assert_eq!(stats.min, i16::MIN.into());
+        assert_eq!(stats.max, u16::MAX.into());
+        assert_eq!(stats.count, 100_001);
+        assert_eq!(stats.mean, 65534);
+    }
+}
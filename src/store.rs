@@ -0,0 +1,40 @@
+//! 最新值存储
+//!
+//! 传感器任务与执行器任务写入最新值，快照上报和查询命令 (`GetSensors` / `GetActuators`) 从这里读取。
+
+use crate::protocol::{
+    ActuatorFeedback, ActuatorStates, ActuatorTag, SensorReading, SensorSnapshot,
+};
+use core::cell::RefCell;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+
+static SENSORS: Mutex<CriticalSectionRawMutex, RefCell<SensorSnapshot>> =
+    Mutex::new(RefCell::new(SensorSnapshot::new()));
+
+static ACTUATORS: Mutex<CriticalSectionRawMutex, RefCell<ActuatorStates>> =
+    Mutex::new(RefCell::new(ActuatorStates::new()));
+
+/// 记录一条传感器读数
+pub fn update_sensor(reading: SensorReading) {
+    SENSORS.lock(|s| s.borrow_mut().record(reading));
+}
+
+/// 所有传感器的最新值
+pub fn sensors() -> SensorSnapshot {
+    SENSORS.lock(|s| *s.borrow())
+}
+
+/// 记录执行器的当前状态
+pub fn set_actuator(feedback: ActuatorFeedback) {
+    ACTUATORS.lock(|a| a.borrow_mut().update(feedback));
+}
+
+/// 所有执行器的当前状态
+pub fn actuators() -> ActuatorStates {
+    ACTUATORS.lock(|a| *a.borrow())
+}
+
+pub fn is_on(actuator: ActuatorTag) -> bool {
+    actuators().get(actuator)
+}
@@ -0,0 +1,337 @@
+//! 流式断帧
+//!
+//! 串口收到的数据按任意边界分块到达 (DMA 半满、空闲线路等)，`FrameParser` 把这些分块
+//! 拼接后切分出完整帧。缓冲区里只保留尚未处理完的字节：完整帧交出后即丢弃，
+//! 校验失败时只丢掉当前 SOF，从下一个字节重新同步，因此坏帧后面紧跟的合法帧不会丢失。
+//! 帧收到一半线路就空闲了时，由调用方超时后 `expire` 丢弃残帧的 SOF，同样从下一个字节重新同步。
+
+use crate::frame::{FrameError, check_frame, frame_len};
+use crate::protocol::SOF;
+
+/// 断帧事件
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event<'a> {
+    /// 一个通过校验的完整帧
+    Frame(&'a [u8]),
+    /// 帧之外的字节 (控制台命令行或噪声)
+    Byte(u8),
+    /// CRC 校验失败，丢弃 SOF 重新同步
+    CrcError,
+    /// 帧头非法，丢弃 SOF 重新同步
+    Resync,
+    /// 帧头声明的长度超过缓冲区，丢弃 SOF 重新同步
+    Overflow,
+}
+
+/// 流式断帧器，`N` 为能接收的最长帧
+pub struct FrameParser<const N: usize> {
+    buf: [u8; N],
+    len: usize,
+    /// 上一次交出的帧长度，下一次取事件时丢弃
+    consumed: usize,
+}
+
+impl<const N: usize> FrameParser<N> {
+    pub const fn new() -> Self {
+        Self {
+            buf: [0; N],
+            len: 0,
+            consumed: 0,
+        }
+    }
+
+    /// 追加收到的数据，返回实际接收的字节数
+    ///
+    /// 缓冲区放不下时只接收一部分，调用方应先用 `next_event` 取完事件再追加剩余部分。
+    /// 取完事件后缓冲区至少有一个字节的空间。
+    pub fn extend(&mut self, data: &[u8]) -> usize {
+        self.release();
+        let n = data.len().min(N - self.len);
+        self.buf[self.len..self.len + n].copy_from_slice(&data[..n]);
+        self.len += n;
+        n
+    }
+
+    /// 取出下一个事件，数据不足以构成事件时返回 `None`
+    pub fn next_event(&mut self) -> Option<Event<'_>> {
+        self.release();
+        let data = &self.buf[..self.len];
+        let first = *data.first()?;
+        if first != SOF {
+            self.discard(1);
+            return Some(Event::Byte(first));
+        }
+
+        // 不必等缓冲区写满，帧头一到就能判断放不下
+        if let Ok(len) = frame_len(data)
+            && len > N
+        {
+            self.discard(1);
+            return Some(Event::Overflow);
+        }
+
+        match check_frame(data) {
+            FrameError::Valid(len) => {
+                self.consumed = len;
+                Some(Event::Frame(&self.buf[..len]))
+            }
+            FrameError::HeaderError => {
+                self.discard(1);
+                Some(Event::Resync)
+            }
+            FrameError::CrcError => {
+                self.discard(1);
+                Some(Event::CrcError)
+            }
+            FrameError::Incomplete => None,
+        }
+    }
+
+    /// 缓冲区里是否有未收完的帧
+    pub fn is_pending(&self) -> bool {
+        self.len > self.consumed
+    }
+
+    /// 未收完的帧等不到后续字节 (线路空闲超时后调用)：丢弃其 SOF，返回是否有未收完的帧
+    ///
+    /// 残帧里剩下的字节可能包含完整的帧，调用方应接着用 `next_event` 取出事件。
+    pub fn expire(&mut self) -> bool {
+        self.release();
+        let pending = self.len > 0;
+        if pending {
+            self.discard(1);
+        }
+        pending
+    }
+
+    /// 丢弃上一次交出的帧
+    fn release(&mut self) {
+        let consumed = core::mem::take(&mut self.consumed);
+        self.discard(consumed);
+    }
+
+    /// 丢弃缓冲区开头的 `n` 个字节
+    fn discard(&mut self, n: usize) {
+        if n > 0 {
+            self.buf.copy_within(n..self.len, 0);
+            self.len -= n;
+        }
+    }
+}
+
+impl<const N: usize> Default for FrameParser<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::frame::{FrameVersion, encode_msg};
+    use crate::protocol::{ActuatorTag, CommandAck, NackReason, TxMessage};
+
+    type Bytes = heapless::Vec<u8, 512>;
+
+    #[derive(Debug, PartialEq)]
+    enum Seen {
+        Frame(heapless::Vec<u8, 64>),
+        Byte(u8),
+        CrcError,
+        Resync,
+        Overflow,
+    }
+
+    type Log = heapless::Vec<Seen, 128>;
+
+    fn frame(msg: TxMessage, version: FrameVersion, seq: u8) -> heapless::Vec<u8, 64> {
+        let mut buf = [0u8; 64];
+        let len = encode_msg(&msg, version, seq, false, &mut buf);
+        heapless::Vec::from_slice(&buf[..len]).unwrap()
+    }
+
+    fn heartbeat(version: FrameVersion, uptime_s: u32) -> heapless::Vec<u8, 64> {
+        frame(TxMessage::Heartbeat { uptime_s }, version, uptime_s as u8)
+    }
+
+    fn drain<const N: usize>(parser: &mut FrameParser<N>, log: &mut Log) {
+        while let Some(event) = parser.next_event() {
+            let seen = match event {
+                Event::Frame(f) => Seen::Frame(heapless::Vec::from_slice(f).unwrap()),
+                Event::Byte(b) => Seen::Byte(b),
+                Event::CrcError => Seen::CrcError,
+                Event::Resync => Seen::Resync,
+                Event::Overflow => Seen::Overflow,
+            };
+            log.push(seen).unwrap();
+        }
+    }
+
+    /// 按给定分块依次输入，返回全部事件
+    fn run<'a, const N: usize>(chunks: impl IntoIterator<Item = &'a [u8]>) -> Log {
+        let mut parser = FrameParser::<N>::new();
+        let mut log = Log::new();
+        for mut chunk in chunks {
+            while !chunk.is_empty() {
+                let n = parser.extend(chunk);
+                chunk = &chunk[n..];
+                drain(&mut parser, &mut log);
+            }
+        }
+        log
+    }
+
+    /// 任意分块方式得到的事件都应与逐字节输入相同
+    fn assert_split_invariant<const N: usize>(stream: &[u8]) -> Log {
+        let expected = run::<N>(stream.chunks(1));
+        for size in 2..=stream.len() {
+            assert_eq!(
+                run::<N>(stream.chunks(size)),
+                expected,
+                "chunk size {}",
+                size
+            );
+        }
+        for cut in 0..=stream.len() {
+            let (a, b) = stream.split_at(cut);
+            assert_eq!(run::<N>([a, b]), expected, "split at {}", cut);
+        }
+        expected
+    }
+
+    #[test]
+    fn frames_split_at_any_boundary() {
+        let a = heartbeat(FrameVersion::V1, 7);
+        let b = heartbeat(FrameVersion::V2, 0xAA);
+        let ack = CommandAck::rejected(ActuatorTag::Pump as u8, NackReason::Busy, 3);
+        let c = frame(TxMessage::Ack(ack), FrameVersion::V2, 3);
+
+        let mut stream = Bytes::new();
+        stream.extend_from_slice(&a).unwrap();
+        stream.extend_from_slice(b"ok\r\n").unwrap();
+        stream.extend_from_slice(&b).unwrap();
+        stream.extend_from_slice(&c).unwrap();
+        // 连续的突发数据超过缓冲区长度也不丢帧
+        for uptime in 0..12 {
+            stream
+                .extend_from_slice(&heartbeat(FrameVersion::V2, uptime))
+                .unwrap();
+        }
+        assert!(stream.len() > 2 * 64);
+
+        let log = assert_split_invariant::<64>(&stream);
+        assert_eq!(log[0], Seen::Frame(a));
+        assert_eq!(log[1..5], [b'o', b'k', b'\r', b'\n'].map(Seen::Byte));
+        assert_eq!(log[5], Seen::Frame(b));
+        assert_eq!(log[6], Seen::Frame(c));
+        assert_eq!(log.len(), 7 + 12);
+        assert!(log[7..].iter().all(|s| matches!(s, Seen::Frame(_))));
+    }
+
+    #[test]
+    fn truncated_frame_does_not_swallow_next() {
+        // 前一帧只收到一半 (线路干扰)，后一帧紧随其后
+        let a = heartbeat(FrameVersion::V2, 1);
+        let b = heartbeat(FrameVersion::V2, 2);
+        let mut stream = Bytes::new();
+        stream.extend_from_slice(&a[..5]).unwrap();
+        stream.extend_from_slice(&b).unwrap();
+
+        let log = assert_split_invariant::<64>(&stream);
+        assert!(log.contains(&Seen::CrcError));
+        assert_eq!(log.last(), Some(&Seen::Frame(b)));
+        assert_eq!(
+            log.iter().filter(|s| matches!(s, Seen::Frame(_))).count(),
+            1
+        );
+    }
+
+    #[test]
+    fn corrupted_frame_resyncs() {
+        let a = heartbeat(FrameVersion::V1, 5);
+        let mut bad = heartbeat(FrameVersion::V2, 6);
+        bad[6] ^= 0x01;
+        let mut stream = Bytes::new();
+        stream.extend_from_slice(&bad).unwrap();
+        // LEN 为 0 的假 SOF
+        stream.extend_from_slice(&[SOF, 0x00, 0x00, 0x01]).unwrap();
+        stream.extend_from_slice(&a).unwrap();
+
+        let log = assert_split_invariant::<64>(&stream);
+        assert_eq!(log[0], Seen::CrcError);
+        assert!(log.contains(&Seen::Resync));
+        assert_eq!(log.last(), Some(&Seen::Frame(a)));
+    }
+
+    #[test]
+    fn oversized_length_skipped_immediately() {
+        // v1 LEN = 0x40，整帧 67 字节，放不下 32 字节的缓冲区
+        let a = heartbeat(FrameVersion::V1, 9);
+        let mut stream = Bytes::new();
+        stream.extend_from_slice(&[SOF, 0x40]).unwrap();
+        stream.extend_from_slice(&a).unwrap();
+
+        let log = assert_split_invariant::<32>(&stream);
+        assert_eq!(
+            log.as_slice(),
+            &[Seen::Overflow, Seen::Byte(0x40), Seen::Frame(a)]
+        );
+    }
+
+    #[test]
+    fn partial_frame_waits_for_more_data() {
+        let a = heartbeat(FrameVersion::V2, 3);
+        let mut parser = FrameParser::<64>::new();
+        assert_eq!(parser.extend(&a[..a.len() - 1]), a.len() - 1);
+        assert_eq!(parser.next_event(), None);
+        parser.extend(&a[a.len() - 1..]);
+        assert_eq!(parser.next_event(), Some(Event::Frame(&a)));
+        assert_eq!(parser.next_event(), None);
+    }
+
+    #[test]
+    fn expire_skips_truncated_frame() {
+        let first = heartbeat(FrameVersion::V2, 1);
+        let second = heartbeat(FrameVersion::V2, 2);
+        let mut parser = FrameParser::<64>::new();
+        let mut log = Log::new();
+
+        // 残帧的 LEN 会把下一帧的开头当作自己的 Payload
+        parser.extend(&first[..first.len() - 2]);
+        drain(&mut parser, &mut log);
+        assert!(log.is_empty());
+        assert!(parser.is_pending());
+
+        assert!(parser.expire());
+        drain(&mut parser, &mut log);
+        assert!(!parser.is_pending());
+        assert!(!parser.expire());
+
+        parser.extend(&second);
+        drain(&mut parser, &mut log);
+        // 残帧剩下的字节按帧外字节交出
+        let (last, rest) = log.split_last().unwrap();
+        assert_eq!(last, &Seen::Frame(second));
+        assert_eq!(rest.len(), first.len() - 3);
+        assert!(rest.iter().all(|seen| matches!(seen, Seen::Byte(_))));
+    }
+
+    #[test]
+    fn expire_keeps_frame_after_truncated_one() {
+        let second = heartbeat(FrameVersion::V2, 2);
+        let mut parser = FrameParser::<64>::new();
+        let mut log = Log::new();
+
+        // 残帧的 LEN 很大，后面完整的一帧被当作它的 Payload
+        parser.extend(&[SOF, 0x82, 0x00, 0x30, 0x13]);
+        parser.extend(&second);
+        drain(&mut parser, &mut log);
+        assert!(log.is_empty());
+
+        // 超时后跳过残帧的 SOF，缓冲区里的完整帧不会丢
+        assert!(parser.expire());
+        drain(&mut parser, &mut log);
+        assert_eq!(log.len(), 5);
+        assert_eq!(log.last(), Some(&Seen::Frame(second)));
+    }
+}
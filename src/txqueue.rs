@@ -0,0 +1,250 @@
+//! 上行发送队列
+//!
+//! 按优先级出队：命令确认和告警最先发出，其次是执行器状态，最后是传感器上报。
+//! 传感器上报不占用队列空间，而是每个传感器 (以及快照) 各保留一个最新值槽位：
+//! 尚未发出的旧读数直接被新读数覆盖，因此发布传感器读数永远不会因为链路阻塞而等待。
+
+use crate::protocol::{SensorData, SensorReading, SensorSnapshot, SensorTag, TxMessage};
+
+/// 上行消息优先级，数值越小越先发送
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    /// 命令确认、控制台回复和安全事件，上位机在等待
+    Urgent,
+    /// 执行器状态、查询结果、心跳等
+    Status,
+    /// 传感器上报，只保留最新值
+    Sensor,
+}
+
+impl Priority {
+    pub fn of(msg: &TxMessage) -> Self {
+        match msg {
+            TxMessage::Ack(_) | TxMessage::SecurityEvent { .. } | TxMessage::Console(_) => {
+                Priority::Urgent
+            }
+            TxMessage::Actuator(_)
+            | TxMessage::ActuatorStates(_)
+            | TxMessage::Heartbeat { .. }
+            | TxMessage::DeviceInfo(_)
+            | TxMessage::Diagnostics(_)
+            | TxMessage::Stats(_) => Priority::Status,
+            TxMessage::Sensor(_) | TxMessage::Snapshot(_) => Priority::Sensor,
+        }
+    }
+}
+
+/// 入队结果
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Pushed {
+    /// 排在队尾
+    Queued,
+    /// 覆盖了同一传感器 (或快照) 尚未发出的旧读数
+    Overwritten,
+}
+
+/// 占位读数，槽位为空时的内容
+const EMPTY: SensorReading = SensorReading {
+    data: SensorData::SoilMoisture(0),
+    timestamp_ms: 0,
+};
+
+/// 快照槽位在 `pending` 中的位
+const SNAPSHOT_BIT: u8 = 1 << SensorTag::ALL.len();
+
+/// 优先级发送队列，`N` 为紧急和状态消息的容量，同一优先级内先进先出
+///
+/// 槽位是否有待发内容记在 `pending` 位图里 (按 `SensorTag::index()`，最高位为快照)，
+/// 而不是用 `Option`：这样初始值全零，作为 static 时放在 .bss 而不占用 Flash。
+pub struct TxQueue<const N: usize> {
+    items: heapless::Vec<TxMessage, N>,
+    sensors: [SensorReading; SensorTag::ALL.len()],
+    snapshot: SensorSnapshot,
+    pending: u8,
+}
+
+impl<const N: usize> TxQueue<N> {
+    pub const fn new() -> Self {
+        Self {
+            items: heapless::Vec::new(),
+            sensors: [EMPTY; SensorTag::ALL.len()],
+            snapshot: SensorSnapshot::new(),
+            pending: 0,
+        }
+    }
+
+    /// 标记槽位待发送，返回此前是否已有待发内容
+    fn mark(&mut self, bit: u8) -> bool {
+        let was_pending = self.pending & bit != 0;
+        self.pending |= bit;
+        was_pending
+    }
+
+    /// 入队；紧急和状态消息在队列已满时原样退回，传感器上报总能成功
+    pub fn push(&mut self, msg: TxMessage) -> Result<Pushed, TxMessage> {
+        let slot_was_full = match msg {
+            TxMessage::Sensor(reading) => {
+                let i = reading.data.tag().index();
+                self.sensors[i] = reading;
+                self.mark(1 << i)
+            }
+            TxMessage::Snapshot(snapshot) => {
+                self.snapshot = snapshot;
+                self.mark(SNAPSHOT_BIT)
+            }
+            _ => {
+                self.items.push(msg)?;
+                false
+            }
+        };
+        Ok(if slot_was_full {
+            Pushed::Overwritten
+        } else {
+            Pushed::Queued
+        })
+    }
+
+    /// 取出优先级最高的消息，同一优先级取最早入队的；传感器上报按 TAG 顺序
+    pub fn pop(&mut self) -> Option<TxMessage> {
+        let first = self
+            .items
+            .iter()
+            .enumerate()
+            .min_by_key(|&(i, msg)| (Priority::of(msg), i))
+            .map(|(i, _)| i);
+        if let Some(i) = first {
+            return Some(self.items.remove(i));
+        }
+        let next = self.pending.trailing_zeros() as usize;
+        if next > SensorTag::ALL.len() {
+            return None;
+        }
+        self.pending &= self.pending - 1;
+        Some(match self.sensors.get(next) {
+            Some(&reading) => TxMessage::Sensor(reading),
+            None => TxMessage::Snapshot(self.snapshot),
+        })
+    }
+}
+
+impl<const N: usize> Default for TxQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::{
+        ActuatorFeedback, ActuatorTag, CommandAck, NackReason, SensorData, SensorReading,
+        SensorSnapshot,
+    };
+
+    fn sensor(data: SensorData) -> TxMessage {
+        TxMessage::Sensor(SensorReading {
+            data,
+            timestamp_ms: 0,
+        })
+    }
+
+    fn ack(seq: u8) -> TxMessage {
+        TxMessage::Ack(CommandAck::rejected(
+            ActuatorTag::Fan as u8,
+            NackReason::Busy,
+            seq,
+        ))
+    }
+
+    fn actuator(state: bool) -> TxMessage {
+        TxMessage::Actuator(ActuatorFeedback {
+            actuator: ActuatorTag::Pump,
+            state,
+            failsafe: false,
+            timestamp_ms: None,
+        })
+    }
+
+    fn is_ack(msg: Option<TxMessage>, seq: u8) -> bool {
+        matches!(msg, Some(TxMessage::Ack(a)) if a.seq == seq)
+    }
+
+    fn is_sensor(msg: Option<TxMessage>, data: SensorData) -> bool {
+        matches!(msg, Some(TxMessage::Sensor(r)) if r.data == data)
+    }
+
+    #[test]
+    fn pops_by_priority_then_fifo() {
+        let mut queue = TxQueue::<8>::new();
+        queue.push(sensor(SensorData::Humidity(1))).unwrap();
+        queue.push(actuator(true)).unwrap();
+        queue.push(ack(1)).unwrap();
+        queue.push(TxMessage::Heartbeat { uptime_s: 5 }).unwrap();
+        queue.push(ack(2)).unwrap();
+
+        assert!(is_ack(queue.pop(), 1));
+        assert!(is_ack(queue.pop(), 2));
+        assert!(matches!(queue.pop(), Some(TxMessage::Actuator(_))));
+        assert!(matches!(queue.pop(), Some(TxMessage::Heartbeat { .. })));
+        assert!(is_sensor(queue.pop(), SensorData::Humidity(1)));
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn keeps_latest_sensor_reading() {
+        let mut queue = TxQueue::<8>::new();
+        assert_eq!(
+            queue.push(sensor(SensorData::Temperature(100))).unwrap(),
+            Pushed::Queued
+        );
+        queue.push(sensor(SensorData::Humidity(1))).unwrap();
+        assert_eq!(
+            queue.push(sensor(SensorData::Temperature(200))).unwrap(),
+            Pushed::Overwritten
+        );
+        queue
+            .push(TxMessage::Snapshot(SensorSnapshot::new()))
+            .unwrap();
+        assert_eq!(
+            queue
+                .push(TxMessage::Snapshot(SensorSnapshot::new()))
+                .unwrap(),
+            Pushed::Overwritten
+        );
+        // 执行器状态每条都要送达，不合并
+        queue.push(actuator(true)).unwrap();
+        assert_eq!(queue.push(actuator(false)).unwrap(), Pushed::Queued);
+
+        queue.pop();
+        queue.pop();
+        assert!(is_sensor(queue.pop(), SensorData::Temperature(200)));
+        assert!(is_sensor(queue.pop(), SensorData::Humidity(1)));
+        assert!(matches!(queue.pop(), Some(TxMessage::Snapshot(_))));
+        assert!(queue.pop().is_none());
+    }
+
+    #[test]
+    fn sensor_reports_never_blocked() {
+        let mut queue = TxQueue::<2>::new();
+        queue.push(actuator(true)).unwrap();
+        queue.push(ack(7)).unwrap();
+        assert!(matches!(
+            queue.push(actuator(false)),
+            Err(TxMessage::Actuator(_))
+        ));
+
+        // 状态消息占满队列时传感器仍然可以发布
+        assert_eq!(
+            queue.push(sensor(SensorData::LightIntensity(1))).unwrap(),
+            Pushed::Queued
+        );
+        assert_eq!(
+            queue.push(sensor(SensorData::LightIntensity(2))).unwrap(),
+            Pushed::Overwritten
+        );
+        assert!(is_ack(queue.pop(), 7));
+        assert!(matches!(queue.pop(), Some(TxMessage::Actuator(_))));
+        assert!(is_sensor(queue.pop(), SensorData::LightIntensity(2)));
+        assert!(queue.pop().is_none());
+    }
+}
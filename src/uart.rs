@@ -1,347 +1,588 @@
-use crate::config::UART_TX_CHANNEL;
-use crate::protocol::{ActuatorTag, MessageType, SOF, SensorData, SensorTag, TxMessage};
+use crate::auth::{AuthError, ReplayGuard};
+use crate::config::{
+    COMMAND_CHANNEL, CONSOLE, ENCRYPTION, HOST_ACK_CHANNEL, NODE_ADDRESS, POLL_REPLY_WINDOW_MS,
+    RELIABLE_UPLINK, REQUIRE_AUTH, RX_FRAME_TIMEOUT_MS, UART_TX_CHANNEL,
+};
+use crate::console::{self, Line, LineBuffer, LineError, Reply};
+use crate::crypt::{self, Direction, ENC_OVERHEAD, MAX_PLAIN_LEN};
+use crate::diag;
+use crate::frame::{
+    BROADCAST_ADDR, FrameParts, FrameVersion, MAX_FRAME_LEN, decode_parts, encode_msg, frame_parts,
+    set_address,
+};
+use crate::info;
+use crate::link;
+use crate::protocol::{
+    AckMode, CommandAck, ConfigOption, ControlCommand, MAX_COMMANDS, MessageType, NackReason,
+    RxMessage, TxMessage,
+};
+use crate::reliable::{AckCache, RetransmitQueue, RetryAction};
+use crate::report;
+use crate::sampling;
+use crate::session;
+use crate::store;
+use crate::stream::{Event, FrameParser};
+use core::cell::{Cell, RefCell};
+use core::sync::atomic::{AtomicBool, AtomicU8, Ordering};
 use embassy_executor::task;
-use embassy_stm32::{mode::Async, usart::UartRx};
-use embassy_time::{Duration, with_timeout};
-
-/// 最小帧长 SOF + LEN + TYPE + CRC (Payload为0时)
-const MIN_FRAME_LEN: usize = 4;
-
-/// 校验结果枚举
-pub enum FrameError {
-    HeaderError,  // 头不对
-    Incomplete,   // 数据没收完
-    CrcError,     // 校验失败
-    Valid(usize), // 合法帧，返回帧的总长度
+use embassy_futures::select::{Either, Either3, select, select3};
+use embassy_stm32::{
+    gpio::Output,
+    mode::Async,
+    usart::{self, UartRx, UartTx},
+};
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::signal::Signal;
+use embassy_time::{Duration, Instant, Timer, with_timeout};
+
+/// 上行帧版本，跟随上位机最近一次发来的合法帧版本 (默认 v1，兼容旧上位机)
+static UPLINK_VERSION: AtomicU8 = AtomicU8::new(FrameVersion::V1 as u8);
+
+/// 上位机是否开启了上报时间戳 (Configure 消息设置，默认关闭)
+static TIMESTAMPS: AtomicBool = AtomicBool::new(false);
+
+/// 控制台无法区分多机总线上的节点，多机模式下禁用 (与认证、加密的冲突由 config 在编译期检查)
+const CONSOLE_ENABLED: bool = CONSOLE && NODE_ADDRESS.is_none();
+
+/// 收到命令行后进入控制台模式，暂停二进制帧输出；收到合法帧或 `exit` 时退出
+static CONSOLE_MODE: AtomicBool = AtomicBool::new(false);
+
+fn console_mode() -> bool {
+    CONSOLE_MODE.load(Ordering::Relaxed)
 }
 
-/// 核心校验函数
-/// data: 收到的原始 buffer
-pub fn check_frame(data: &[u8]) -> FrameError {
-    let received_len = data.len();
-
-    // 1. 基础长度检查
-    if received_len < MIN_FRAME_LEN {
-        return FrameError::Incomplete;
-    }
+/// 多机总线上最近一次被上位机轮询 (收到发给本机的帧) 的时刻
+static POLLED_AT: Mutex<CriticalSectionRawMutex, Cell<Option<Instant>>> =
+    Mutex::new(Cell::new(None));
+
+/// 多机总线上只在被轮询后的回复窗口内发送，免得与其它节点的上行帧冲突
+fn may_transmit() -> bool {
+    let window = Duration::from_millis(POLL_REPLY_WINDOW_MS);
+    NODE_ADDRESS.is_none()
+        || POLLED_AT
+            .lock(|t| t.get())
+            .is_some_and(|t| t.elapsed() < window)
+}
 
-    // 2. 检查 SOF (帧头)
-    if data[0] != SOF {
-        return FrameError::HeaderError;
+fn uplink_version() -> FrameVersion {
+    if UPLINK_VERSION.load(Ordering::Relaxed) == FrameVersion::V2 as u8 {
+        FrameVersion::V2
+    } else {
+        FrameVersion::V1
     }
+}
 
-    // 3. 计算理论上的总长度
-    // LEN 字段 = TYPE(1) + PAYLOAD(N)
-    // 总帧长 = SOF(1) + LEN字段(1) + LEN的值 + CRC(1)
-    let body_len = data[1] as usize;
-    let expected_total_len = 1 + 1 + body_len + 1;
-
-    // 4. 检查是否接收完整
-    if received_len < expected_total_len {
-        return FrameError::Incomplete;
+/// 实际使用的上行帧版本：加密或带节点地址时只能用 v2
+fn tx_version() -> FrameVersion {
+    if ENCRYPTION || NODE_ADDRESS.is_some() {
+        FrameVersion::V2
+    } else {
+        uplink_version()
     }
+}
 
-    // 5. 校验 CRC
-    // 范围：从 SOF 到 PAYLOAD 结束
-    let frame_content = &data[0..expected_total_len - 1];
-    let received_crc = data[expected_total_len - 1];
-
-    let calculated_crc = calculate_crc(frame_content);
+/// 串口发送端，RS-485 收发器的 DE/RE 引脚在发送期间拉高
+pub struct Port {
+    tx: UartTx<'static, Async>,
+    de: Output<'static>,
+}
 
-    if calculated_crc != received_crc {
-        return FrameError::CrcError;
+impl Port {
+    pub fn new(tx: UartTx<'static, Async>, de: Output<'static>) -> Self {
+        Self { tx, de }
     }
 
-    FrameError::Valid(expected_total_len)
-}
-
-/// 简单的异或校验 (XOR Checksum)
-fn calculate_crc(data: &[u8]) -> u8 {
-    let mut crc = 0;
-    for &byte in data {
-        crc ^= byte;
+    pub async fn write(&mut self, data: &[u8]) -> Result<(), usart::Error> {
+        self.de.set_high();
+        // DMA 完成时最后一个字节还在移位寄存器里，等 TC 后再释放总线
+        let result = match self.tx.write(data).await {
+            Ok(()) => self.tx.flush().await,
+            Err(e) => Err(e),
+        };
+        self.de.set_low();
+        result
     }
-    crc
 }
 
+/// `de`: RS-485 收发器的 DE/RE 方向引脚 (高电平发送)，点对点连接时悬空即可
 #[task]
-pub async fn uart_tx_task(mut tx: embassy_stm32::usart::UartTx<'static, Async>) {
-    let receiver = UART_TX_CHANNEL.receiver();
+pub async fn uart_tx_task(tx: UartTx<'static, Async>, de: Output<'static>) {
+    let mut port = Port::new(tx, de);
+    let receiver = &UART_TX_CHANNEL;
+    let host_ack_receiver = HOST_ACK_CHANNEL.receiver();
+    // 上行帧序号，每帧递增 (ACK 帧回显命令序号，不占用)
+    let mut seq: u8 = 0;
+    // 等待上位机确认的可靠帧
+    let mut pending = RetransmitQueue::new();
+    // 加密帧计数器，每个加密帧 (含重传) 递增
+    let mut enc_counter: u32 = 0;
+
     loop {
-        let msg = receiver.receive().await;
-        // 最大帧长估计：SensorReport 有 4 个传感器数据，每个 3 byte (tag+len+val?) no, value is 8 bytes in TLVItem but defined strictly.
-        // Let's simple buffer
-        let mut buffer = [0u8; 64];
-        let len = encode_msg(&msg, &mut buffer);
-
-        if len > 0 {
-            // 发送
-            if let Err(e) = tx.write(&buffer[..len]).await {
-                crate::fmt::warn!("UART TX Error: {}", e);
+        let retry_timer = async {
+            match pending.next_deadline() {
+                Some(deadline) => Timer::at(Instant::from_millis(deadline)).await,
+                None => core::future::pending().await,
             }
-        }
-    }
-}
-
-fn encode_msg(msg: &TxMessage, buffer: &mut [u8]) -> usize {
-    // 构造 Payload
-    // Frame: SOF, LEN, TYPE, Payload..., CRC
-    // Index: 0    1    2     3...
-
-    buffer[0] = SOF;
-    // buffer[1] = LEN (filled later)
-    // buffer[2] = TYPE (filled later)
+        };
 
-    let mut payload_idx = 3;
-    let msg_type;
+        match select3(receiver.receive(), host_ack_receiver.receive(), retry_timer).await {
+            Either3::First(TxMessage::Console(reply)) => {
+                let text = console::render(&reply);
+                if let Err(e) = port.write(text.as_bytes()).await {
+                    crate::fmt::warn!("UART TX Error: {}", e);
+                    diag::incr(&diag::TX_ERRORS);
+                }
+            }
+            Either3::First(_) if console_mode() => {
+                // 控制台模式下不输出二进制帧，免得终端里出现乱码
+            }
+            Either3::First(msg) => {
+                send_frame(&mut port, &msg, seq, &mut enc_counter).await;
+
+                // 可靠模式依赖 v2 帧的序号
+                if RELIABLE_UPLINK
+                    && tx_version() == FrameVersion::V2
+                    && msg.needs_host_ack()
+                    && !pending.track(seq, msg, Instant::now().as_millis())
+                {
+                    crate::fmt::warn!("Reliable queue full, seq {} sent once", seq);
+                }
 
-    match msg {
-        TxMessage::Sensor(data) => {
-            msg_type = MessageType::SensorReport;
-            match data {
-                SensorData::SoilMoisture(val) => append_tlv_u16(
-                    buffer,
-                    &mut payload_idx,
-                    SensorTag::SoilMoisture as u8,
-                    *val,
-                ),
-                SensorData::Temperature(val) => {
-                    append_tlv_i16(buffer, &mut payload_idx, SensorTag::Temperature as u8, *val)
+                if !matches!(msg, TxMessage::Ack(_)) {
+                    seq = seq.wrapping_add(1);
                 }
-                SensorData::Humidity(val) => {
-                    append_tlv_u16(buffer, &mut payload_idx, SensorTag::Humidity as u8, *val)
+            }
+            Either3::Second(acked) => {
+                pending.ack(acked);
+            }
+            Either3::Third(()) => {
+                while let Some(action) = pending.poll(Instant::now().as_millis()) {
+                    match action {
+                        RetryAction::Resend(seq, msg) => {
+                            if !console_mode() {
+                                diag::incr(&diag::TX_RETRANSMITS);
+                                send_frame(&mut port, &msg, seq, &mut enc_counter).await;
+                            }
+                        }
+                        RetryAction::GiveUp(seq) => {
+                            diag::incr(&diag::TX_GIVE_UPS);
+                            crate::fmt::warn!("No HostAck for seq {}, giving up", seq);
+                        }
+                    }
                 }
-                SensorData::LightIntensity(val) => append_tlv_u16(
-                    buffer,
-                    &mut payload_idx,
-                    SensorTag::LightIntensity as u8,
-                    *val,
-                ),
             }
         }
-        TxMessage::Actuator(status) => {
-            msg_type = MessageType::ActuatorStatus;
-            // Tag
-            buffer[payload_idx] = status.actuator as u8;
-            payload_idx += 1;
-            // Len
-            buffer[payload_idx] = 1;
-            payload_idx += 1;
-            // Value
-            buffer[payload_idx] = if status.state { 1 } else { 0 };
-            payload_idx += 1;
-        }
-        TxMessage::Ack(ack) => {
-            msg_type = MessageType::CommandAck;
-            // Tag
-            buffer[payload_idx] = ack.actuator as u8;
-            payload_idx += 1;
-            // Len
-            buffer[payload_idx] = 1;
-            payload_idx += 1;
-            // Value (0x01 Success, 0x00 Fail)
-            buffer[payload_idx] = if ack.success { 1 } else { 0 };
-            payload_idx += 1;
-        }
-        TxMessage::Heartbeat => {
-            msg_type = MessageType::Heartbeat;
-        }
     }
+}
 
-    buffer[2] = msg_type as u8;
-
-    // Calculate LEN = TYPE(1) + PAYLOAD
-    let payload_len = payload_idx - 3; // buffer[3] is start of payload
-    let total_body_len = 1 + payload_len;
-    buffer[1] = total_body_len as u8;
-
-    // CRC
-    let crc_idx = payload_idx;
-    buffer[crc_idx] = calculate_crc(&buffer[0..crc_idx]);
+/// 编码并发送一帧，加密模式下就地加密
+async fn send_frame(port: &mut Port, msg: &TxMessage, seq: u8, enc_counter: &mut u32) {
+    // 最长的是带时间戳的快照帧，加密后再加 COUNTER 和 TAG
+    let mut buffer = [0u8; 96];
+    let timestamps = TIMESTAMPS.load(Ordering::Relaxed);
+
+    // DeviceInfo 始终明文发送，上位机要用其中的上电随机数派生会话密钥
+    let key = match session::key() {
+        Some(key) if ENCRYPTION && !matches!(msg, TxMessage::DeviceInfo(_)) => Some(key),
+        _ => None,
+    };
+    if !may_transmit() {
+        crate::fmt::debug!("Not polled, uplink frame dropped");
+        return;
+    }
+    let mut len = encode_msg(msg, tx_version(), seq, timestamps, &mut buffer);
 
-    crc_idx + 1 // Total length
-}
+    // 地址要在加密前加上，受 AEAD 保护
+    if let Some(addr) = NODE_ADDRESS
+        && len > 0
+    {
+        len = set_address(&mut buffer, len, addr);
+    }
 
-fn append_tlv_u16(buffer: &mut [u8], idx: &mut usize, tag: u8, val: u16) {
-    buffer[*idx] = tag;
-    *idx += 1;
-    buffer[*idx] = 2; // Len
-    *idx += 1;
-    let bytes = val.to_be_bytes();
-    buffer[*idx] = bytes[0];
-    *idx += 1;
-    buffer[*idx] = bytes[1];
-    *idx += 1;
-}
+    if let Some(key) = key
+        && len > 0
+    {
+        if len + ENC_OVERHEAD > buffer.len() {
+            crate::fmt::warn!("Frame too long to encrypt, dropped");
+            return;
+        }
+        *enc_counter = enc_counter.wrapping_add(1);
+        len = crypt::seal_frame(key, Direction::Uplink, *enc_counter, &mut buffer, len);
+    }
 
-fn append_tlv_i16(buffer: &mut [u8], idx: &mut usize, tag: u8, val: i16) {
-    buffer[*idx] = tag;
-    *idx += 1;
-    buffer[*idx] = 2; // Len
-    *idx += 1;
-    let bytes = val.to_be_bytes();
-    buffer[*idx] = bytes[0];
-    *idx += 1;
-    buffer[*idx] = bytes[1];
-    *idx += 1;
+    if len > 0 {
+        // 发送
+        if let Err(e) = port.write(&buffer[..len]).await {
+            crate::fmt::warn!("UART TX Error: {}", e);
+            diag::incr(&diag::TX_ERRORS);
+        }
+    }
 }
 
 #[task]
-pub async fn uart_rx_task(mut rx: UartRx<'static, Async>) {
-    let mut buffer = [0u8; 128];
-    // Start index of valid data
-    let mut valid_start = 0;
-    // End index of valid data
-    let mut valid_end = 0;
-
-    // Command Sender
-    let cmd_sender = crate::config::COMMAND_CHANNEL.sender();
+pub async fn uart_rx_task(rx: UartRx<'static, Async>) {
+    // 循环 DMA 在后台持续接收，任务处理帧期间到达的数据留在环形缓冲区里
+    let mut dma_buf = [0u8; 128];
+    let mut rx = rx.into_ring_buffered(&mut dma_buf);
+    let mut chunk = [0u8; 32];
+    let mut parser = FrameParser::<MAX_FRAME_LEN>::new();
+
+    // 认证帧的重放保护
+    let mut replay_guard = ReplayGuard::new();
+    // 控制台命令行
+    let mut lines = LineBuffer::new();
 
     loop {
-        // Read into buffer after valid_end
-        // We must ensure we have space
-        if valid_end >= buffer.len() {
-            // Buffer full, discard or shift?
-            // Should have shifted already. If full here, means we have a huge packet or garbage.
-            // Reset.
-            valid_start = 0;
-            valid_end = 0;
-        }
-
-        match with_timeout(
-            Duration::from_secs(1),
-            rx.read_until_idle(&mut buffer[valid_end..]),
-        )
-        .await
-        {
-            Ok(Ok(len)) => {
-                if len == 0 {
-                    continue;
-                }
-                valid_end += len;
+        // 帧收到一半时限定下一批字节的等待时间，超时说明发送方中途停了 (掉电、线路干扰)
+        let partial = RX_FRAME_TIMEOUT_MS > 0 && parser.is_pending();
+        let idle = async {
+            if partial {
+                Timer::after_millis(RX_FRAME_TIMEOUT_MS).await
+            } else {
+                core::future::pending().await
             }
-            Ok(Err(e)) => {
+        };
+        let len = match select(rx.read(&mut chunk), idle).await {
+            Either::First(Ok(len)) => len,
+            Either::First(Err(e)) => {
+                // 出错后 DMA 停止，下一次 read 时重新启动；丢失的字节由断帧器重新同步
                 crate::fmt::warn!("RX Error: {}", e);
+                diag::incr(&diag::RX_ERRORS);
                 continue;
             }
-            Err(_) => {
-                // Timeout
+            Either::Second(()) => {
+                // 跳过残帧的 SOF，其后已收到的字节照常断帧
+                crate::fmt::warn!("Partial frame timed out, resyncing");
+                parser.expire();
+                diag::incr(&diag::RX_RESYNCS);
+                0
             }
-        }
+        };
 
-        // Process buffer
-        while valid_end - valid_start >= MIN_FRAME_LEN {
-            let data_slice = &buffer[valid_start..valid_end];
-
-            // Check potential frame
-            match check_frame(data_slice) {
-                FrameError::Valid(len) => {
-                    // Valid frame found at valid_start with length len
-                    let frame_len = len;
-                    let frame = &buffer[valid_start..valid_start + frame_len];
-
-                    // Parse Frame
-                    // Frame: SOF(1) LEN(1) TYPE(1) Payload(N) CRC(1)
-                    let msg_type_byte = frame[2];
-                    let payload_len = frame[1] as usize - 1; // LEN = TYPE + Payload
-                    let payload = &frame[3..3 + payload_len];
-
-                    if msg_type_byte == MessageType::Command as u8 {
-                        // Parse Command Payload
-                        parse_and_send_commands(payload, &cmd_sender).await;
+        let mut data = &chunk[..len];
+        loop {
+            while let Some(event) = parser.next_event() {
+                match event {
+                    Event::Frame(frame) => {
+                        // 多机总线上发给其它节点的帧 (包括其它节点的上行帧) 直接丢弃
+                        if let Some(broadcast) = addressing(frame_parts(frame).addr) {
+                            if !broadcast {
+                                // 上位机在等本机回复，打开回复窗口
+                                POLLED_AT.lock(|t| t.set(Some(Instant::now())));
+                            }
+                            handle_frame(frame, broadcast, &mut replay_guard).await;
+                        }
                     }
-
-                    // Consume frame
-                    valid_start += len;
-                }
-                FrameError::HeaderError => {
-                    // Current byte is not SOF, skip 1
-                    valid_start += 1;
-                }
-                FrameError::CrcError => {
-                    // Header looked ok (SOF correct), but CRC failed.
-                    // It might be a false SOF detection or corrupted frame.
-                    // Skip 1 byte and try to resync.
-                    valid_start += 1;
-                }
-                FrameError::Incomplete => {
-                    // Need more data
-                    break;
+                    // 帧之外的字节可能是终端里输入的命令行
+                    Event::Byte(byte) => {
+                        if CONSOLE_ENABLED && let Some(line) = lines.push(byte) {
+                            console_line(line).await;
+                        }
+                    }
+                    Event::CrcError => diag::incr(&diag::RX_CRC_ERRORS),
+                    Event::Resync => diag::incr(&diag::RX_RESYNCS),
+                    Event::Overflow => diag::incr(&diag::RX_OVERFLOWS),
                 }
             }
+            if data.is_empty() {
+                break;
+            }
+            let taken = parser.extend(data);
+            data = &data[taken..];
         }
+    }
+}
 
-        // Compact buffer
-        if valid_start > 0 {
-            if valid_start == valid_end {
-                valid_start = 0;
-                valid_end = 0;
-            } else {
-                // only move if necessary or if close to end
-                if valid_end > 100 {
-                    let len = valid_end - valid_start;
-                    buffer.copy_within(valid_start..valid_end, 0);
-                    valid_start = 0;
-                    valid_end = len;
-                }
+/// 处理一行控制台命令，回复以文本发出
+async fn console_line(line: Result<&str, LineError>) {
+    let parsed = line.and_then(console::parse);
+    // 二进制链路上的噪声也可能凑成一行，只有合法命令才进入控制台模式
+    if parsed.is_err() && !console_mode() {
+        return;
+    }
+    CONSOLE_MODE.store(true, Ordering::Relaxed);
+    let reply = match parsed {
+        Ok(Line::Command(cmd)) => {
+            link::frame_received();
+            // 执行结果由命令任务以文本回复
+            COMMAND_CHANNEL.send(cmd).await;
+            return;
+        }
+        Ok(Line::GetSensors) => Reply::Sensors(store::sensors()),
+        Ok(Line::GetActuators) => Reply::Actuators(store::actuators()),
+        Ok(Line::Help) => Reply::Help,
+        Ok(Line::Exit) => {
+            CONSOLE_MODE.store(false, Ordering::Relaxed);
+            Reply::Bye
+        }
+        Err(e) => Reply::Error(e),
+    };
+    UART_TX_CHANNEL.send(TxMessage::Console(reply)).await;
+}
+
+/// 按帧地址判断是否处理：不是发给本机的帧返回 None，否则返回是否按广播处理 (执行但不回复)
+fn addressing(addr: Option<u8>) -> Option<bool> {
+    match (addr, NODE_ADDRESS) {
+        (Some(BROADCAST_ADDR), _) => Some(true),
+        // 本机未配置地址：点对点连接，照常回复
+        (_, None) => Some(false),
+        (Some(addr), Some(node)) => (addr == node).then_some(false),
+        // 多机总线上不带地址的帧 (如 v1 帧) 所有节点都会收到，与广播一样不回复
+        (None, Some(_)) => Some(true),
+    }
+}
+
+/// 认证、解码并处理一个发给本机的合法帧
+async fn handle_frame(frame: &[u8], broadcast: bool, replay_guard: &mut ReplayGuard) {
+    let mut plain = [0u8; MAX_PLAIN_LEN];
+    match authenticate(frame, replay_guard, &mut plain) {
+        Ok(parts) => {
+            link::frame_received();
+            CONSOLE_MODE.store(false, Ordering::Relaxed);
+            let version = parts.version;
+            UPLINK_VERSION.store(version as u8, Ordering::Relaxed);
+
+            match decode_parts(&parts) {
+                Ok(msg) => dispatch(msg, version, parts.payload, broadcast).await,
+                Err(e) => crate::fmt::warn!("Frame decode error: {}", e),
+            }
+        }
+        Err(reason) => {
+            // 伪造/重放的帧不算链路活动，也不执行
+            crate::fmt::warn!("Rejected frame: {}", reason);
+            diag::incr(&diag::AUTH_FAILURES);
+            if !broadcast {
+                let counter = frame_counter(&frame_parts(frame));
+                // 不阻塞接收，避免被伪造帧洪泛拖住
+                let _ = UART_TX_CHANNEL.try_send(TxMessage::SecurityEvent { reason, counter });
             }
         }
     }
 }
 
-async fn parse_and_send_commands(
-    payload: &[u8],
-    sender: &embassy_sync::channel::Sender<
-        '_,
-        embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex,
-        crate::protocol::ControlCommand,
-        4,
-    >,
-) {
-    let mut i = 0;
-    while i < payload.len() {
-        if i + 2 > payload.len() {
-            break;
-        } // Need at least Tag + Len
-        let tag = payload[i];
-        let len = payload[i + 1];
-        let val_start = i + 2;
-        let val_end = val_start + len as usize;
-
-        if val_end > payload.len() {
-            break;
+/// 校验认证帧、解密加密帧，返回明文帧
+///
+/// `REQUIRE_AUTH` 时改变设备状态的消息 (Command / Configure / SetInterval / SetDeadband / HostAck、
+/// 带清零的 GetDiagnostics) 必须带认证；`ENCRYPTION` 时除 GetDeviceInfo 外必须加密
+fn authenticate<'a>(
+    frame: &'a [u8],
+    guard: &mut ReplayGuard,
+    plain: &'a mut [u8],
+) -> Result<FrameParts<'a>, AuthError> {
+    let parts = frame_parts(frame);
+    if parts.encrypted {
+        let key = session::key().ok_or(AuthError::Missing)?;
+        let opened = crypt::open_frame(key, Direction::Downlink, frame, plain)?;
+        // 加密帧与认证帧共用同一个计数器
+        guard.check(opened.counter, opened.mac, parts.msg_type)?;
+        return Ok(FrameParts {
+            payload: opened.payload,
+            auth: None,
+            encrypted: false,
+            ..parts
+        });
+    }
+    if parts.auth.is_some() {
+        let key = session::auth_key().ok_or(AuthError::Missing)?;
+        guard.verify(key, &parts)?;
+        return Ok(parts);
+    }
+
+    let msg_type = MessageType::from(parts.msg_type);
+    // 加密模式下只有 GetDeviceInfo 允许明文，上位机靠它拿到上电随机数
+    if ENCRYPTION && !matches!(msg_type, MessageType::GetDeviceInfo) {
+        return Err(AuthError::Missing);
+    }
+    let needs_auth = match msg_type {
+        MessageType::Command
+        | MessageType::Configure
+        | MessageType::SetInterval
+        | MessageType::SetDeadband
+        | MessageType::HostAck => true,
+        // 清零会抹掉 AUTH_FAILURES 等计数，只读查询不需要认证
+        MessageType::GetDiagnostics => {
+            matches!(
+                decode_parts(&parts),
+                Ok(RxMessage::GetDiagnostics { reset: true })
+            )
         }
+        _ => false,
+    };
+    if REQUIRE_AUTH && needs_auth {
+        Err(AuthError::Missing)
+    } else {
+        Ok(parts)
+    }
+}
 
-        let value_bytes = &payload[val_start..val_end];
+/// 被拒绝帧携带的计数器，随 SecurityEvent 上报
+fn frame_counter(parts: &FrameParts) -> Option<u32> {
+    if let Some(auth) = &parts.auth {
+        return Some(auth.counter);
+    }
+    if parts.encrypted {
+        let bytes = parts.payload.get(..4)?;
+        return Some(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]));
+    }
+    None
+}
+
+/// 回复上位机；广播帧由多个节点同时收到，一律不回复以免总线冲突
+async fn respond(msg: TxMessage, broadcast: bool) {
+    if !broadcast {
+        UART_TX_CHANNEL.send(msg).await;
+    }
+}
 
-        // Try to map Tag to Actuator
-        let actuator = ActuatorTag::from(tag);
-        // Note: ActuatorTag::from defaults to Fan if unknown.
-        // Ideally we should check if tag is valid.
-        // Assuming valid for now or filtered by ActuatorTag definition range if we improved From impl.
+/// 最近一条命令帧已回复的 ACK，上位机重传该帧时原样重发
+static ACK_CACHE: Mutex<CriticalSectionRawMutex, RefCell<AckCache>> =
+    Mutex::new(RefCell::new(AckCache::new()));
 
-        // Heuristic:
-        // Len == 1 => State (0/1)
-        // Len == 2 => Duration (u16)
+/// 重发 ACK 前等待第一次的命令处理完的最长时间
+const ACK_WAIT_MS: u64 = 100;
 
-        let mut cmd = crate::protocol::ControlCommand {
-            actuator,
-            state: false,
-            duration_ms: 0,
-        };
+/// 有 ACK 记入缓存时通知等待重发的接收任务
+static ACK_RECORDED: Signal<CriticalSectionRawMutex, ()> = Signal::new();
 
-        if len == 1 {
-            cmd.state = value_bytes[0] != 0;
-            // duration default 0
-            sender.send(cmd).await;
-        } else if len == 2 {
-            let val = u16::from_be_bytes([value_bytes[0], value_bytes[1]]);
-            cmd.duration_ms = val;
-            cmd.state = true; // Duration implies ON?
-            sender.send(cmd).await;
-        }
+/// 回复命令的 ACK，同时记入缓存
+pub async fn send_ack(ack: CommandAck) {
+    ACK_CACHE.lock(|c| c.borrow_mut().record(ack));
+    ACK_RECORDED.signal(());
+    UART_TX_CHANNEL.send(TxMessage::Ack(ack)).await;
+}
 
-        // Move to next TLV
-        i = val_end;
+/// 处理一条解码后的下行消息，`payload` 用于识别重传的命令帧
+async fn dispatch(msg: RxMessage, version: FrameVersion, payload: &[u8], broadcast: bool) {
+    match msg {
+        RxMessage::Command { seq, commands } => {
+            // v1 帧没有序号，无法判重；广播命令不回复 ACK
+            let expected = if broadcast { 0 } else { commands.len() };
+            if version == FrameVersion::V2
+                && ACK_CACHE.lock(|c| c.borrow_mut().begin(seq, payload, expected))
+            {
+                // 上位机重传了已执行的命令：不再重复执行，原样重发第一次回复的 ACK。
+                // 第一次的命令可能还在命令任务里排队，先等它们的 ACK 都记录下来
+                let _ = with_timeout(Duration::from_millis(ACK_WAIT_MS), async {
+                    while !ACK_CACHE.lock(|c| c.borrow().complete()) {
+                        ACK_RECORDED.wait().await;
+                    }
+                })
+                .await;
+                let acks: heapless::Vec<CommandAck, MAX_COMMANDS> =
+                    ACK_CACHE.lock(|c| c.borrow().acks().iter().copied().collect());
+                for ack in acks {
+                    respond(TxMessage::Ack(ack), broadcast).await;
+                }
+                return;
+            }
+
+            for entry in commands {
+                match entry {
+                    Ok(cmd) => {
+                        let ack = if broadcast {
+                            AckMode::Silent
+                        } else {
+                            AckMode::Frame
+                        };
+                        let cmd = ControlCommand { ack, ..cmd };
+                        COMMAND_CHANNEL.send(cmd).await
+                    }
+                    // 解码阶段即被拒绝 (未知 TAG、长度错误)
+                    Err(nack) if !broadcast => send_ack(nack).await,
+                    Err(_) => {}
+                }
+            }
+        }
+        RxMessage::Configure { seq, options } => {
+            for entry in options {
+                let ack = match entry {
+                    Ok(option) => {
+                        match option {
+                            ConfigOption::Timestamps(on) => TIMESTAMPS.store(on, Ordering::Relaxed),
+                            ConfigOption::StatsWindow(s) => report::set_stats_window(s),
+                        }
+                        CommandAck {
+                            tag: option.tag() as u8,
+                            success: true,
+                            reason: NackReason::None,
+                            seq,
+                        }
+                    }
+                    Err(nack) => nack,
+                };
+                respond(TxMessage::Ack(ack), broadcast).await;
+            }
+        }
+        RxMessage::SetInterval { seq, intervals } => {
+            for entry in intervals {
+                let ack = match entry {
+                    Ok(interval) => {
+                        let tag = interval.sensor as u8;
+                        match sampling::set_interval(interval.sensor, interval.interval_ms) {
+                            Ok(()) => CommandAck {
+                                tag,
+                                success: true,
+                                reason: NackReason::None,
+                                seq,
+                            },
+                            Err(reason) => CommandAck::rejected(tag, reason, seq),
+                        }
+                    }
+                    Err(nack) => nack,
+                };
+                respond(TxMessage::Ack(ack), broadcast).await;
+            }
+        }
+        RxMessage::SetDeadband { seq, deadbands } => {
+            for entry in deadbands {
+                let ack = match entry {
+                    Ok(setting) => {
+                        report::set_deadband(setting.sensor, setting.band);
+                        CommandAck {
+                            tag: setting.sensor as u8,
+                            success: true,
+                            reason: NackReason::None,
+                            seq,
+                        }
+                    }
+                    Err(nack) => nack,
+                };
+                respond(TxMessage::Ack(ack), broadcast).await;
+            }
+        }
+        RxMessage::HostAck { seq } => {
+            // v1 帧没有序号，不参与可靠传输
+            if version == FrameVersion::V2 {
+                let _ = HOST_ACK_CHANNEL.try_send(seq);
+            }
+        }
+        RxMessage::Heartbeat { .. } => {
+            // 链路活动已在收到合法帧时记录，下位机自己周期发送心跳，无需回复；
+            // 上位机会定期发心跳，此后才做链路超时判断
+            link::arm();
+        }
+        RxMessage::GetSensors => {
+            respond(TxMessage::Snapshot(store::sensors()), broadcast).await;
+        }
+        RxMessage::GetActuators => {
+            respond(TxMessage::ActuatorStates(store::actuators()), broadcast).await;
+        }
+        RxMessage::GetDeviceInfo => {
+            respond(TxMessage::DeviceInfo(info::device_info()), broadcast).await;
+        }
+        RxMessage::GetDiagnostics { reset } => {
+            respond(TxMessage::Diagnostics(diag::snapshot(reset)), broadcast).await;
+        }
+        RxMessage::SensorReport(_)
+        | RxMessage::ActuatorStatus(_)
+        | RxMessage::CommandAck(_)
+        | RxMessage::SecurityEvent { .. }
+        | RxMessage::DeviceInfo { .. }
+        | RxMessage::Diagnostics(_)
+        | RxMessage::SensorStats(_) => {
+            // 上行消息类型，下位机收到时忽略
+            crate::fmt::debug!("Ignoring uplink-only message from host");
+        }
     }
 }
@@ -0,0 +1,93 @@
+//! 上行发送通道
+//!
+//! 用法与 `embassy_sync::channel::Channel` 相同，但内部是 `txqueue::TxQueue`：
+//! TX 任务总是先取出优先级最高的消息。传感器上报只保留每个传感器的最新值，`try_send` 总能成功，
+//! 采样任务不会被链路拖住；被覆盖的旧读数计入 `diag::SENSOR_OVERWRITES`。
+
+use crate::diag;
+use crate::protocol::TxMessage;
+use crate::txqueue::{Pushed, TxQueue};
+use core::cell::RefCell;
+use core::future::poll_fn;
+use core::task::Poll;
+use embassy_sync::blocking_mutex::Mutex;
+use embassy_sync::blocking_mutex::raw::CriticalSectionRawMutex;
+use embassy_sync::waitqueue::{MultiWakerRegistration, WakerRegistration};
+
+struct State<const N: usize> {
+    queue: TxQueue<N>,
+    receiver: WakerRegistration,
+    /// 等待空位的发送方，登记满时全部唤醒重新竞争
+    senders: MultiWakerRegistration<4>,
+}
+
+pub struct Uplink<const N: usize> {
+    state: Mutex<CriticalSectionRawMutex, RefCell<State<N>>>,
+}
+
+impl<const N: usize> Uplink<N> {
+    pub const fn new() -> Self {
+        Self {
+            state: Mutex::new(RefCell::new(State {
+                queue: TxQueue::new(),
+                receiver: WakerRegistration::new(),
+                senders: MultiWakerRegistration::new(),
+            })),
+        }
+    }
+
+    /// 立即入队，队列已满时退回 (传感器上报不会被退回)
+    pub fn try_send(&self, msg: TxMessage) -> Result<(), TxMessage> {
+        self.state.lock(|s| {
+            let mut s = s.borrow_mut();
+            if s.queue.push(msg)? == Pushed::Overwritten {
+                diag::incr(&diag::SENSOR_OVERWRITES);
+            }
+            s.receiver.wake();
+            Ok(())
+        })
+    }
+
+    /// 入队，队列已满时等待 TX 任务取走消息
+    pub async fn send(&self, msg: TxMessage) {
+        poll_fn(|cx| {
+            self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+                match s.queue.push(msg) {
+                    Ok(pushed) => {
+                        if pushed == Pushed::Overwritten {
+                            diag::incr(&diag::SENSOR_OVERWRITES);
+                        }
+                        s.receiver.wake();
+                        Poll::Ready(())
+                    }
+                    Err(_) => {
+                        s.senders.register(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+
+    /// 取出优先级最高的消息
+    pub async fn receive(&self) -> TxMessage {
+        poll_fn(|cx| {
+            self.state.lock(|s| {
+                let mut s = s.borrow_mut();
+                match s.queue.pop() {
+                    Some(msg) => {
+                        s.senders.wake();
+                        Poll::Ready(msg)
+                    }
+                    None => {
+                        s.receiver.register(cx.waker());
+                        Poll::Pending
+                    }
+                }
+            })
+        })
+        .await
+    }
+}